@@ -1,8 +1,5 @@
-pub(super) mod client_action;
 pub(super) mod composition;
-pub(super) mod full_width;
 pub(super) mod input_mode;
 pub(super) mod ipc_service;
-pub(super) mod roman2kana;
+pub(super) mod key_event;
 pub(super) mod state;
-pub(super) mod user_action;
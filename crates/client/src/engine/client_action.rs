@@ -1,24 +0,0 @@
-use super::input_mode::InputMode;
-
-#[derive(Debug, PartialEq)]
-pub enum ClientAction {
-    StartComposition,
-    EndComposition,
-
-    AppendText(String),
-    RemoveText,
-
-    MoveCursor(i32),
-    SetSelection(SetSelectionType),
-
-    SetIMEMode(InputMode),
-
-    ShrinkText,
-}
-
-#[derive(Debug, PartialEq)]
-pub enum SetSelectionType {
-    Up,
-    Down,
-    Number(i32),
-}
@@ -7,29 +7,29 @@ use windows::{
 
 use anyhow::Result;
 
-use super::{client_action::ClientAction, composition::CompositionState, state::IMEState};
+use ime_engine::{
+    client_action::ClientAction, composition::CompositionState, input_mode::InputMode,
+};
 
-#[derive(Default, Clone, PartialEq, Debug)]
-pub enum InputMode {
-    #[default]
-    Latin,
-    Kana,
-}
+use super::state::IMEState;
 
 impl TextServiceFactory {
     pub fn set_input_mode(&self, mode: InputMode) -> Result<()> {
-        {
-            let mut ime_state = IMEState::get()?;
-            ime_state.input_mode = mode;
+        // the composition is committed in the current mode, as the toggle key does
+        let actions = vec![ClientAction::SetIMEMode(mode)];
 
-            // update the language bar
-            self.update_lang_bar()?;
-        }
+        self.handle_action(&actions, CompositionState::None)?;
 
-        // stop the composition
-        let actions = vec![ClientAction::EndComposition];
+        Ok(())
+    }
 
-        self.handle_action(&actions, CompositionState::None)?;
+    // change the mode without touching the composition
+    pub fn switch_input_mode(&self, mode: InputMode) -> Result<()> {
+        let mut ime_state = IMEState::get()?;
+        ime_state.input_mode = mode;
+
+        // update the language bar
+        self.update_lang_bar()?;
 
         Ok(())
     }
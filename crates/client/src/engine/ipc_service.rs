@@ -1,23 +1,144 @@
+use ime_engine::{
+    backend::{Backend, Snapshot},
+    offline::OfflineBackend,
+    settings::{ConversionStyle, InputStyle, Settings},
+};
 use protos::proto::{
-    azookey_service_client::AzookeyServiceClient, window_service_client::WindowServiceClient,
+    action::{ClearText, Kind},
+    azookey_service_client::AzookeyServiceClient,
+    window_service_client::WindowServiceClient,
+};
+use std::{
+    collections::HashMap,
+    future::Future,
+    os::windows::process::CommandExt as _,
+    path::PathBuf,
+    process::{Child, Command},
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+use windows::{
+    core::Interface as _,
+    Win32::{System::Threading::CREATE_NO_WINDOW, UI::TextServices::ITfContext},
 };
-use std::sync::Arc;
 
-// connect to kkc server
+use crate::globals::DllModule;
+
+// the servers are connected again in the background after they are lost,
+// waiting twice as long after each failure
+const FIRST_RETRY: Duration = Duration::from_millis(250);
+const LAST_RETRY: Duration = Duration::from_secs(10);
+// about a minute, a server which is not up by then is not launched again until the focus changes
+const MAX_ATTEMPTS: u32 = 10;
+// a server which doesn't answer by then is taken as hung, and the request is handled offline
+// the first conversion loads the dictionary, so this is not much shorter
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Server {
+    // kkc server
+    Azookey,
+    // candidate window server
+    Window,
+}
+
+impl Server {
+    fn name(self) -> &'static str {
+        match self {
+            Server::Azookey => "azookey-server",
+            Server::Window => "ui",
+        }
+    }
+
+    fn address(self) -> &'static str {
+        match self {
+            Server::Azookey => "http://[::1]:50051",
+            Server::Window => "http://[::1]:50052",
+        }
+    }
+
+    fn executable(self, settings: &Settings) -> anyhow::Result<PathBuf> {
+        let configured = match self {
+            Server::Azookey => &settings.server_path,
+            Server::Window => &settings.ui_path,
+        };
+        if let Some(path) = configured {
+            return Ok(path.clone());
+        }
+
+        let dll = PathBuf::from(DllModule::get_path()?);
+        Ok(dll.with_file_name(format!("{}.exe", self.name())))
+    }
+}
+
 #[derive(Debug, Clone)]
-pub struct IPCService {
+struct Connection {
     // kkc server client
     azookey_client: AzookeyServiceClient<tonic::transport::channel::Channel>,
     // candidate window server client
     window_client: WindowServiceClient<tonic::transport::channel::Channel>,
-    runtime: Arc<tokio::runtime::Runtime>,
+    // sessions of an older connection are unknown to the server
+    generation: u64,
+}
+
+#[derive(Debug, Default)]
+struct Link {
+    connection: Option<Connection>,
+    generation: u64,
+    reconnecting: bool,
 }
 
-#[derive(Debug, Clone, Default)]
-pub struct Candidates {
-    pub texts: Vec<String>,
-    pub sub_texts: Vec<String>,
-    pub corresponding_count: Vec<i32>,
+// the reading of the composition of a context, replayed when the backend which has it is gone
+#[derive(Debug, Default)]
+struct Composing {
+    offline: OfflineBackend,
+    spell: String,
+    cursor: i32,
+    // generation of the connection which has the reading, 0 for the offline backend
+    generation: u64,
+}
+
+impl Composing {
+    // actions which restore the reading on the backend of the generation
+    // the backend may still have an older composition, so it is cleared first
+    fn replay(&self, generation: u64) -> Vec<Kind> {
+        if self.generation == generation {
+            return vec![];
+        }
+
+        let mut actions = vec![Kind::ClearText(ClearText {})];
+        if !self.spell.is_empty() {
+            let len = self.spell.chars().count() as i32;
+            actions.push(Kind::AppendText(self.spell.clone()));
+            actions.push(Kind::MoveCursor(self.cursor - len));
+        }
+        actions
+    }
+
+    fn update(&mut self, snapshot: &Snapshot, generation: u64) {
+        self.spell = snapshot.spell.clone();
+        self.cursor = snapshot.cursor;
+        self.generation = generation;
+    }
+}
+
+// connect to kkc server, or convert to hiragana locally while it is unreachable
+#[derive(Debug, Clone)]
+pub struct IPCService {
+    runtime: Arc<tokio::runtime::Runtime>,
+    // shared by the clones, so that any of them can go offline and back
+    link: Arc<Mutex<Link>>,
+    // the composition of each context, keyed as the sessions are
+    // the session ids are not stable, a new one is opened after reconnecting and none offline
+    composing: Arc<Mutex<HashMap<usize, Composing>>>,
+    // the pointer of the focused ITfContext
+    context: usize,
+    // session of the focused context, sent with every request to the kkc server
+    session_id: u64,
+    // sessions opened for each context, keyed by the pointer of ITfContext
+    sessions: HashMap<usize, u64>,
+    // connection generation which the sessions belong to
+    generation: u64,
 }
 
 impl Default for IPCService {
@@ -27,189 +148,388 @@ impl Default for IPCService {
                 log::error!("Failed to create runtime: {:#}", e);
             })
             .unwrap();
-        let azookey_client = runtime
-            .block_on(AzookeyServiceClient::connect("http://[::1]:50051"))
-            .map_err(|e| {
-                log::error!("Failed to connect to server: {:#}", e);
-            })
-            .unwrap();
-        let window_client = runtime
-            .block_on(WindowServiceClient::connect("http://[::1]:50052"))
-            .map_err(|e| {
-                log::error!("Failed to connect to server: {:#}", e);
-            })
-            .unwrap();
-        log::debug!("Connected to server: {:?}", azookey_client);
 
-        Self {
-            azookey_client,
-            window_client,
+        let service = Self {
             runtime: Arc::new(runtime),
+            link: Arc::new(Mutex::new(Link::default())),
+            composing: Arc::new(Mutex::new(HashMap::new())),
+            context: 0,
+            session_id: 0,
+            sessions: HashMap::new(),
+            generation: 0,
+        };
+
+        if let Err((server, e)) = service.connect() {
+            log::warn!(
+                "{} is unreachable, typing hiragana offline: {}",
+                server.name(),
+                e
+            );
+            service.reconnect();
         }
+
+        service
     }
 }
 
-// implement methods to interact with kkc server
+// the server is gone, or it was restarted and has lost the sessions
+// any other error, such as a panic in a request, is returned by the server which is still alive
+fn is_broken(status: &tonic::Status) -> bool {
+    matches!(
+        status.code(),
+        tonic::Code::Unavailable | tonic::Code::NotFound
+    )
+}
+
+// implement methods to keep the connection to the servers
 impl IPCService {
-    pub fn append_text(&mut self, text: String) -> anyhow::Result<Candidates> {
-        let request = tonic::Request::new(protos::proto::AppendTextRequest {
-            text_to_append: text,
-        });
+    // the error tells which server is not running
+    fn connect(&self) -> Result<(), (Server, anyhow::Error)> {
+        let azookey_client = self
+            .wait(AzookeyServiceClient::connect(Server::Azookey.address()))
+            .map_err(|e| (Server::Azookey, e))?;
+        let window_client = self
+            .wait(WindowServiceClient::connect(Server::Window.address()))
+            .map_err(|e| (Server::Window, e))?;
+        log::debug!("Connected to server: {:?}", azookey_client);
 
-        let response = self
-            .runtime
-            .clone()
-            .block_on(self.azookey_client.append_text(request))?;
-        let composing_text = response.into_inner().composing_text;
-
-        let candidates = if let Some(composing_text) = composing_text {
-            Candidates {
-                texts: composing_text
-                    .suggestions
-                    .iter()
-                    .map(|s| s.text.clone())
-                    .collect(),
-                sub_texts: composing_text
-                    .suggestions
-                    .iter()
-                    .map(|s| s.subtext.clone())
-                    .collect(),
-                corresponding_count: composing_text
-                    .suggestions
-                    .iter()
-                    .map(|s| s.corresponding_count)
-                    .collect(),
-            }
-        } else {
-            anyhow::bail!("composing_text is None");
-        };
+        if let Ok(mut link) = self.link() {
+            link.generation += 1;
+            link.connection = Some(Connection {
+                azookey_client,
+                window_client,
+                generation: link.generation,
+            });
+        }
 
-        Ok(candidates)
+        Ok(())
     }
 
-    pub fn remove_text(&mut self) -> anyhow::Result<Candidates> {
-        let request = tonic::Request::new(protos::proto::RemoveTextRequest {});
-        let response = self
-            .runtime
-            .clone()
-            .block_on(self.azookey_client.remove_text(request))?;
-        let composing_text = response.into_inner().composing_text;
-
-        let candidates = if let Some(composing_text) = composing_text {
-            Candidates {
-                texts: composing_text
-                    .suggestions
-                    .iter()
-                    .map(|s| s.text.clone())
-                    .collect(),
-                sub_texts: composing_text
-                    .suggestions
-                    .iter()
-                    .map(|s| s.subtext.clone())
-                    .collect(),
-                corresponding_count: composing_text
-                    .suggestions
-                    .iter()
-                    .map(|s| s.corresponding_count)
-                    .collect(),
+    // retry in the background until both servers are back, launching the ones not running
+    // gives up after MAX_ATTEMPTS, e.g. when the executable is missing
+    fn reconnect(&self) {
+        match self.link() {
+            Ok(mut link) if !link.reconnecting => link.reconnecting = true,
+            _ => return,
+        }
+
+        let service = self.clone();
+        std::thread::spawn(move || {
+            let mut launched: HashMap<Server, Child> = HashMap::new();
+            let mut retry = FIRST_RETRY;
+            let mut connected = false;
+
+            for _ in 0..MAX_ATTEMPTS {
+                match service.connect() {
+                    Ok(()) => {
+                        connected = true;
+                        break;
+                    }
+                    Err((server, e)) => {
+                        log::debug!("Failed to reconnect to {}: {}", server.name(), e);
+                        if let Err(e) = launch(server, &mut launched) {
+                            log::warn!("Failed to launch {}: {:#}", server.name(), e);
+                        }
+                    }
+                }
+
+                std::thread::sleep(retry);
+                retry = (retry * 2).min(LAST_RETRY);
             }
-        } else {
-            anyhow::bail!("composing_text is None");
+
+            if let Ok(mut link) = service.link() {
+                link.reconnecting = false;
+            }
+            if connected {
+                log::info!("Server is back online");
+            } else {
+                log::error!(
+                    "Gave up reconnecting, typing hiragana offline until the focus changes"
+                );
+            }
+        });
+    }
+
+    // called when a request fails, the following requests are handled offline
+    fn disconnect(&self, error: &dyn std::fmt::Display) {
+        let Ok(mut link) = self.link() else {
+            return;
         };
+        if link.connection.take().is_some() {
+            log::warn!("Lost the server, typing hiragana offline: {}", error);
+        }
+        drop(link);
 
-        Ok(candidates)
+        self.reconnect();
     }
 
-    pub fn clear_text(&mut self) -> anyhow::Result<()> {
-        let request = tonic::Request::new(protos::proto::ClearTextRequest {});
-        let _response = self
+    // block on a connection, giving up after the timeout
+    fn wait<T, E: Into<anyhow::Error>>(
+        &self,
+        future: impl Future<Output = Result<T, E>>,
+    ) -> anyhow::Result<T> {
+        match self
             .runtime
-            .clone()
-            .block_on(self.azookey_client.clear_text(request))?;
+            .block_on(async { tokio::time::timeout(REQUEST_TIMEOUT, future).await })
+        {
+            Ok(result) => result.map_err(Into::into),
+            Err(_) => anyhow::bail!("timed out"),
+        }
+    }
 
-        Ok(())
+    // block on a request, a server which doesn't answer in time is unavailable as a lost one is
+    fn request<T>(
+        &self,
+        request: impl Future<Output = Result<T, tonic::Status>>,
+    ) -> Result<T, tonic::Status> {
+        self.runtime
+            .block_on(async { tokio::time::timeout(REQUEST_TIMEOUT, request).await })
+            .unwrap_or_else(|_| Err(tonic::Status::unavailable("request timed out")))
     }
 
-    pub fn shrink_text(&mut self, offset: i32) -> anyhow::Result<Candidates> {
-        let request = tonic::Request::new(protos::proto::ShrinkTextRequest { offset });
-        let response = self
-            .runtime
-            .clone()
-            .block_on(self.azookey_client.shrink_text(request))?;
-        let composing_text = response.into_inner().composing_text;
-
-        let candidates = if let Some(composing_text) = composing_text {
-            Candidates {
-                texts: composing_text
-                    .suggestions
-                    .iter()
-                    .map(|s| s.text.clone())
-                    .collect(),
-                sub_texts: composing_text
-                    .suggestions
-                    .iter()
-                    .map(|s| s.subtext.clone())
-                    .collect(),
-                corresponding_count: composing_text
-                    .suggestions
-                    .iter()
-                    .map(|s| s.corresponding_count)
-                    .collect(),
-            }
-        } else {
-            anyhow::bail!("composing_text is None");
-        };
+    fn link(&self) -> anyhow::Result<MutexGuard<'_, Link>> {
+        self.link
+            .lock()
+            .map_err(|_| anyhow::anyhow!("connection state is poisoned"))
+    }
+
+    // the composition of the focused context
+    // the lock is not held across a request, which may block until the timeout
+    fn with_composing<T>(&self, f: impl FnOnce(&mut Composing) -> T) -> anyhow::Result<T> {
+        let mut compositions = self.composing()?;
+        Ok(f(compositions.entry(self.context).or_default()))
+    }
 
-        Ok(candidates)
+    fn composing(&self) -> anyhow::Result<MutexGuard<'_, HashMap<usize, Composing>>> {
+        self.composing
+            .lock()
+            .map_err(|_| anyhow::anyhow!("composing state is poisoned"))
+    }
+
+    fn connection(&self) -> anyhow::Result<Option<Connection>> {
+        Ok(self.link()?.connection.clone())
+    }
+
+    // the connection which the sessions were opened on, if it is still alive
+    fn online_connection(&self) -> anyhow::Result<Option<Connection>> {
+        let connection = self.connection()?;
+        Ok(connection.filter(|connection| connection.generation == self.generation))
     }
 }
 
-// implement methods to interact with candidate window server
+// start the executable of the server, unless the one launched before is still starting up
+fn launch(server: Server, launched: &mut HashMap<Server, Child>) -> anyhow::Result<()> {
+    if let Some(child) = launched.get_mut(&server) {
+        if child.try_wait()?.is_none() {
+            return Ok(());
+        }
+    }
+
+    let path = server.executable(&Settings::load())?;
+    let child = Command::new(&path)
+        .creation_flags(CREATE_NO_WINDOW.0)
+        .spawn()?;
+    log::info!("Launched {}", path.display());
+    launched.insert(server, child);
+
+    Ok(())
+}
+
+// implement methods to manage sessions of kkc server
 impl IPCService {
-    pub fn show_window(&mut self) -> anyhow::Result<()> {
-        let request = tonic::Request::new(protos::proto::EmptyResponse {});
-        self.runtime
-            .clone()
-            .block_on(self.window_client.show_window(request))?;
+    // switch to the session of the context, a new session is opened for an unknown context
+    pub fn activate_context(
+        &mut self,
+        context: &ITfContext,
+        settings: &Settings,
+    ) -> anyhow::Result<()> {
+        let key = context.as_raw() as usize;
+        let focused = self.context != key;
+        self.context = key;
+
+        let Some(connection) = self.connection()? else {
+            // the servers may have been started by hand after the last attempts gave up
+            if focused {
+                self.reconnect();
+            }
+            self.session_id = 0;
+            return Ok(());
+        };
+        if connection.generation != self.generation {
+            self.sessions.clear();
+            self.generation = connection.generation;
+        }
+
+        let session_id = match self.sessions.get(&key) {
+            Some(session_id) => *session_id,
+            None => match self.open_session(connection, settings) {
+                Ok(session_id) => {
+                    self.sessions.insert(key, session_id);
+                    session_id
+                }
+                Err(e) => {
+                    self.disconnect(&e);
+                    0
+                }
+            },
+        };
+        self.session_id = session_id;
 
         Ok(())
     }
 
-    pub fn hide_window(&mut self) -> anyhow::Result<()> {
-        let request = tonic::Request::new(protos::proto::EmptyResponse {});
-        self.runtime
-            .clone()
-            .block_on(self.window_client.hide_window(request))?;
+    pub fn release_context(&mut self, context: &ITfContext) -> anyhow::Result<()> {
+        let key = context.as_raw() as usize;
+        self.composing()?.remove(&key);
+
+        if let Some(session_id) = self.sessions.remove(&key) {
+            if self.session_id == session_id {
+                self.session_id = 0;
+            }
+            if let Some(connection) = self.online_connection()? {
+                self.close_session(connection, session_id)?;
+            }
+        }
 
         Ok(())
     }
 
-    pub fn set_window_position(&mut self, x: i32, y: i32) -> anyhow::Result<()> {
-        let request = tonic::Request::new(protos::proto::SetPositionRequest {
-            position: Some(protos::proto::WindowPosition { x, y }),
-        });
-        self.runtime
-            .clone()
-            .block_on(self.window_client.set_window_position(request))?;
+    pub fn release_all_contexts(&mut self) -> anyhow::Result<()> {
+        let session_ids: Vec<u64> = self.sessions.drain().map(|(_, id)| id).collect();
+        self.session_id = 0;
+        self.composing()?.clear();
+
+        if let Some(connection) = self.online_connection()? {
+            for session_id in session_ids {
+                self.close_session(connection.clone(), session_id)?;
+            }
+        }
 
         Ok(())
     }
 
-    pub fn set_candidates(&mut self, candidates: Vec<String>) -> anyhow::Result<()> {
-        let request = tonic::Request::new(protos::proto::SetCandidateRequest { candidates });
-        self.runtime
-            .clone()
-            .block_on(self.window_client.set_candidate(request))?;
+    fn open_session(&self, mut connection: Connection, settings: &Settings) -> anyhow::Result<u64> {
+        let conversion_style = match settings.conversion_style {
+            ConversionStyle::Live => protos::proto::ConversionStyle::Live,
+            ConversionStyle::Explicit => protos::proto::ConversionStyle::Explicit,
+        };
+        let input_style = match settings.input_style {
+            InputStyle::Romaji => protos::proto::InputStyle::Romaji,
+            InputStyle::Kana => protos::proto::InputStyle::Kana,
+        };
+        let request = tonic::Request::new(protos::proto::OpenSessionRequest {
+            page_size: settings.page_size,
+            conversion_style: conversion_style as i32,
+            input_style: input_style as i32,
+        });
+        let response = self.request(connection.azookey_client.open_session(request))?;
+        let session_id = response.into_inner().session_id;
+        log::debug!("Session opened: {}", session_id);
 
-        Ok(())
+        Ok(session_id)
     }
 
-    pub fn set_selection(&mut self, index: i32) -> anyhow::Result<()> {
-        let request = tonic::Request::new(protos::proto::SetSelectionRequest { index });
-        self.runtime
-            .clone()
-            .block_on(self.window_client.set_selection(request))?;
+    fn close_session(&self, mut connection: Connection, session_id: u64) -> anyhow::Result<()> {
+        let request = tonic::Request::new(protos::proto::CloseSessionRequest { session_id });
+        self.request(connection.azookey_client.close_session(request))?;
+        log::debug!("Session closed: {}", session_id);
 
         Ok(())
     }
 }
+
+// implement methods to interact with kkc server
+impl Backend for IPCService {
+    // apply actions in one round trip, kkc server updates the candidate window by itself
+    // the reading is replayed first if the composition was started on another backend
+    fn process_key(&mut self, actions: Vec<Kind>) -> anyhow::Result<Snapshot> {
+        if let Some(mut connection) = self.online_connection()? {
+            let mut replayed =
+                self.with_composing(|composing| composing.replay(connection.generation))?;
+            replayed.extend(actions.iter().cloned());
+
+            let request = tonic::Request::new(protos::proto::ProcessKeyRequest {
+                session_id: self.session_id,
+                actions: replayed
+                    .into_iter()
+                    .map(|kind| protos::proto::Action { kind: Some(kind) })
+                    .collect(),
+            });
+            match self.request(connection.azookey_client.process_key(request)) {
+                Ok(response) => {
+                    let Some(snapshot) = response.into_inner().snapshot else {
+                        anyhow::bail!("snapshot is None");
+                    };
+                    let snapshot = Snapshot::from(snapshot);
+                    self.with_composing(|composing| {
+                        composing.update(&snapshot, connection.generation)
+                    })?;
+                    return Ok(snapshot);
+                }
+                Err(e) if is_broken(&e) => self.disconnect(&e),
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        self.with_composing(|composing| -> anyhow::Result<Snapshot> {
+            let mut replayed = composing.replay(0);
+            replayed.extend(actions);
+            let snapshot = composing.offline.process_key(replayed)?;
+            composing.update(&snapshot, 0);
+
+            Ok(snapshot)
+        })?
+    }
+
+    fn reverse_lookup(&mut self, text: &str) -> anyhow::Result<Snapshot> {
+        if let Some(mut connection) = self.online_connection()? {
+            let request = tonic::Request::new(protos::proto::ReverseLookupRequest {
+                session_id: self.session_id,
+                text: text.to_string(),
+            });
+            match self.request(connection.azookey_client.reverse_lookup(request)) {
+                Ok(response) => {
+                    let Some(snapshot) = response.into_inner().snapshot else {
+                        anyhow::bail!("snapshot is None");
+                    };
+                    let snapshot = Snapshot::from(snapshot);
+                    self.with_composing(|composing| {
+                        composing.update(&snapshot, connection.generation)
+                    })?;
+                    return Ok(snapshot);
+                }
+                Err(e) if is_broken(&e) => self.disconnect(&e),
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        self.with_composing(|composing| -> anyhow::Result<Snapshot> {
+            let snapshot = composing.offline.reverse_lookup(text)?;
+            composing.update(&snapshot, 0);
+
+            Ok(snapshot)
+        })?
+    }
+}
+
+// implement methods to interact with candidate window server
+impl IPCService {
+    // there is no candidate window while offline
+    pub fn set_window_position(&mut self, x: i32, y: i32) -> anyhow::Result<()> {
+        let Some(mut connection) = self.online_connection()? else {
+            return Ok(());
+        };
+
+        let request = tonic::Request::new(protos::proto::SetPositionRequest {
+            position: Some(protos::proto::WindowPosition { x, y }),
+        });
+        match self.request(connection.window_client.set_window_position(request)) {
+            Ok(_) => Ok(()),
+            Err(e) if is_broken(&e) => {
+                self.disconnect(&e);
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use crate::extension::VKeyExt;
+use anyhow::Result;
+use ime_engine::{
+    kana_input::jis_kana,
+    keymap::{Key, KeyCode, KeyEvent},
+};
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    GetKeyboardState, ToUnicode, VK_CONTROL, VK_SHIFT,
+};
+
+// decode a virtual key code into a key stroke for the keymap, None if the key means nothing to the ime
+// with kana, the keys type the kana printed on a jis keyboard instead of the character
+pub fn from_key_code(key_code: usize, kana: bool) -> Result<Option<KeyEvent>> {
+    let code = match key_code {
+        0x08 => KeyCode::Backspace, // VK_BACK
+        0x09 => KeyCode::Tab,       // VK_TAB
+        0x0D => KeyCode::Enter,     // VK_RETURN
+        0x1B => KeyCode::Escape,    // VK_ESCAPE
+        0x1C => KeyCode::Henkan,    // VK_CONVERT
+        0x20 => KeyCode::Space,     // VK_SPACE
+
+        0x21 => KeyCode::PageUp,   // VK_PRIOR
+        0x22 => KeyCode::PageDown, // VK_NEXT
+        0x23 => KeyCode::End,      // VK_END
+        0x24 => KeyCode::Home,     // VK_HOME
+        0x25 => KeyCode::Left,     // VK_LEFT
+        0x26 => KeyCode::Up,       // VK_UP
+        0x27 => KeyCode::Right,    // VK_RIGHT
+        0x28 => KeyCode::Down,     // VK_DOWN
+        0x2E => KeyCode::Delete,   // VK_DELETE
+
+        0x70..=0x7B => KeyCode::Function((key_code - 0x6F) as u8), // VK_F1 ~ VK_F12
+
+        0xF3 | 0xF4 => KeyCode::Zenkaku, // Zenkaku/Hankaku
+
+        // keys with a character are bound by the character typed without modifiers
+        _ => match to_unicode(key_code, &[0u8; 256]) {
+            Some(c) => KeyCode::Char(c),
+            None => return Ok(None),
+        },
+    };
+
+    let key = Key {
+        code,
+        shift: VK_SHIFT.is_pressed(),
+        ctrl: VK_CONTROL.is_pressed(),
+    };
+
+    let text = match code {
+        KeyCode::Char(_) if !key.ctrl => match kana.then(|| jis_kana(key_code, key.shift)) {
+            Some(Some(kana)) => Some(kana),
+            _ => {
+                let mut key_state = [0u8; 256];
+                unsafe {
+                    GetKeyboardState(&mut key_state)?;
+                }
+                to_unicode(key_code, &key_state)
+            }
+        },
+        _ => None,
+    };
+
+    Ok(Some(KeyEvent { key, text }))
+}
+
+fn to_unicode(key_code: usize, key_state: &[u8; 256]) -> Option<char> {
+    let mut unicode = [0u16; 1];
+    unsafe { ToUnicode(key_code as u32, 0, Some(key_state), &mut unicode, 0) };
+    char::from_u32(unicode[0] as u32).filter(|c| *c != '\0' && !c.is_control())
+}
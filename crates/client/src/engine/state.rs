@@ -5,12 +5,16 @@ use std::{
 
 use windows::{core::GUID, Win32::UI::TextServices::ITfContext};
 
-use super::{input_mode::InputMode, ipc_service::IPCService};
+use ime_engine::{input_mode::InputMode, keymap::Keymap, settings::Settings};
+
+use super::ipc_service::IPCService;
 
 #[derive(Default, Debug)]
 pub struct IMEState {
     pub ipc_service: IPCService,
     pub input_mode: InputMode,
+    pub settings: Settings,
+    pub keymap: Keymap,
     pub cookies: HashMap<GUID, u32>,
     pub context: Option<ITfContext>,
 }
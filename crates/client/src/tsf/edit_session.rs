@@ -4,9 +4,9 @@ use windows::{
         Foundation::{E_FAIL, RECT},
         UI::TextServices::{
             ITfCompositionSink, ITfContext, ITfContextComposition, ITfEditSession,
-            ITfEditSession_Impl, ITfInsertAtSelection, GUID_PROP_ATTRIBUTE, TF_AE_NONE,
-            TF_ANCHOR_END, TF_ANCHOR_START, TF_ES_READWRITE, TF_IAS_QUERYONLY, TF_SELECTION,
-            TF_SELECTIONSTYLE, TF_ST_CORRECTION,
+            ITfEditSession_Impl, ITfInsertAtSelection, ITfRange, GUID_PROP_ATTRIBUTE, TF_AE_NONE,
+            TF_ANCHOR_END, TF_ANCHOR_START, TF_DEFAULT_SELECTION, TF_ES_READWRITE,
+            TF_IAS_QUERYONLY, TF_SELECTION, TF_SELECTIONSTYLE, TF_ST_CORRECTION, TF_TF_MOVESTART,
         },
     },
 };
@@ -54,6 +54,22 @@ impl ITfEditSession_Impl for EditSession_Impl {
     }
 }
 
+// the range of the first selection in the document
+unsafe fn selection_range(context: &ITfContext, cookie: u32) -> Result<Option<ITfRange>> {
+    let mut selection = [TF_SELECTION {
+        range: ManuallyDrop::new(None),
+        style: TF_SELECTIONSTYLE {
+            ase: TF_AE_NONE,
+            fInterimChar: false.into(),
+        },
+    }];
+    let mut fetched: u32 = 0;
+    context.GetSelection(cookie, TF_DEFAULT_SELECTION, &mut selection, &mut fetched)?;
+
+    let range = ManuallyDrop::take(&mut selection[0].range);
+    Ok(range.filter(|_| fetched > 0))
+}
+
 impl TextServiceFactory {
     pub fn start_composition(&self) -> Result<()> {
         log::debug!("start_composition");
@@ -83,17 +99,152 @@ impl TextServiceFactory {
             )?;
         }
 
-        self.borrow_mut()?.borrow_mut_composition()?.tip_composition = composition.borrow().clone();
+        self.borrow()?
+            .set_tip_composition(composition.borrow().clone())?;
         log::debug!("Composition started {composition:?}");
 
         Ok(())
     }
 
+    // start a composition over the text just before the caret, only if it is the given text
+    pub fn start_composition_over(&self, text: &str) -> Result<bool> {
+        log::debug!("start_composition_over");
+        let composition = Rc::new(RefCell::new(None));
+
+        {
+            let text_service = self.borrow()?;
+            let context = text_service.context()?;
+            let context_composition = text_service.context::<ITfContextComposition>()?;
+            let sink = text_service.this::<ITfCompositionSink>()?;
+            let insert = text_service.context::<ITfInsertAtSelection>()?;
+            let expected = text.to_wide_16_unpadded();
+
+            edit_session(
+                text_service.tid,
+                context,
+                Rc::new({
+                    let composition_ref = Rc::clone(&composition);
+                    move |cookie| unsafe {
+                        let range = insert.InsertTextAtSelection(cookie, TF_IAS_QUERYONLY, &[])?;
+                        range.Collapse(cookie, TF_ANCHOR_START)?;
+                        let mut shifted: i32 = 0;
+                        range.ShiftStart(
+                            cookie,
+                            -(expected.len() as i32),
+                            &mut shifted,
+                            std::ptr::null(),
+                        )?;
+
+                        // the caret may have been moved since the commit
+                        let mut buffer = vec![0u16; expected.len()];
+                        let mut fetched: u32 = 0;
+                        range.GetText(cookie, 0, &mut buffer, &mut fetched)?;
+                        if buffer[..fetched as usize] != expected[..] {
+                            return Ok(());
+                        }
+
+                        let composition =
+                            context_composition.StartComposition(cookie, &range, &sink)?;
+                        *composition_ref.borrow_mut() = Some(composition);
+                        Ok(())
+                    }
+                }),
+            )?;
+        }
+
+        let composition = composition.borrow().clone();
+        let started = composition.is_some();
+        if started {
+            self.borrow()?.set_tip_composition(composition)?;
+        }
+        log::debug!("Composition started over {text:?}: {started}");
+
+        Ok(started)
+    }
+
+    // the text of the first selection, empty if nothing is selected
+    pub fn selected_text(&self) -> Result<String> {
+        let selected = Rc::new(RefCell::new(String::new()));
+
+        {
+            let text_service = self.borrow()?;
+            let context = text_service.context::<ITfContext>()?;
+
+            edit_session(
+                text_service.tid,
+                text_service.context()?,
+                Rc::new({
+                    let selected_ref = Rc::clone(&selected);
+                    move |cookie| unsafe {
+                        let Some(range) = selection_range(&context, cookie)? else {
+                            return Ok(());
+                        };
+
+                        // the start of the range moves forward as the text is read
+                        let mut text = vec![];
+                        let mut buffer = [0u16; 256];
+                        loop {
+                            let mut fetched: u32 = 0;
+                            range.GetText(cookie, TF_TF_MOVESTART, &mut buffer, &mut fetched)?;
+                            text.extend_from_slice(&buffer[..fetched as usize]);
+                            if (fetched as usize) < buffer.len() {
+                                break;
+                            }
+                        }
+
+                        *selected_ref.borrow_mut() = String::from_utf16_lossy(&text);
+                        Ok(())
+                    }
+                }),
+            )?;
+        }
+
+        let selected = selected.borrow().clone();
+        Ok(selected)
+    }
+
+    // start a composition over the first selection, for the reconversion
+    pub fn start_composition_over_selection(&self) -> Result<()> {
+        log::debug!("start_composition_over_selection");
+        let composition = Rc::new(RefCell::new(None));
+
+        {
+            let text_service = self.borrow()?;
+            let context = text_service.context::<ITfContext>()?;
+            let context_composition = text_service.context::<ITfContextComposition>()?;
+            let sink = text_service.this::<ITfCompositionSink>()?;
+
+            edit_session(
+                text_service.tid,
+                text_service.context()?,
+                Rc::new({
+                    let composition_ref = Rc::clone(&composition);
+                    move |cookie| unsafe {
+                        let Some(range) = selection_range(&context, cookie)? else {
+                            return Ok(());
+                        };
+                        let composition =
+                            context_composition.StartComposition(cookie, &range, &sink)?;
+
+                        *composition_ref.borrow_mut() = Some(composition);
+                        Ok(())
+                    }
+                }),
+            )?;
+        }
+
+        self.borrow()?
+            .set_tip_composition(composition.borrow().clone())?;
+        log::debug!("Composition started over selection {composition:?}");
+
+        Ok(())
+    }
+
     pub fn end_composition(&self) -> Result<()> {
         log::debug!("end_composition");
         let text_service = self.borrow()?;
 
-        if let Some(composition) = text_service.borrow_composition()?.tip_composition.clone() {
+        if let Some(composition) = text_service.tip_composition()? {
             edit_session(
                 text_service.tid,
                 text_service.context()?,
@@ -114,7 +265,7 @@ impl TextServiceFactory {
     pub fn set_text(&self, text: &str, subtext: &str) -> Result<()> {
         let text_service = self.borrow()?;
 
-        if let Some(composition) = text_service.borrow_composition()?.tip_composition.clone() {
+        if let Some(composition) = text_service.tip_composition()? {
             edit_session(
                 text_service.tid,
                 text_service.context()?,
@@ -167,7 +318,7 @@ impl TextServiceFactory {
     pub fn set_cursor(&self, position: i32) -> Result<()> {
         let text_service = self.borrow()?;
 
-        if let Some(composition) = text_service.borrow_composition()?.tip_composition.clone() {
+        if let Some(composition) = text_service.tip_composition()? {
             edit_session(
                 text_service.tid,
                 text_service.context()?,
@@ -207,10 +358,9 @@ impl TextServiceFactory {
 
     pub fn get_and_send_pos(&self) -> Result<()> {
         let text_service = self.borrow()?;
-        let composition = text_service.borrow_composition()?;
 
         // I don't want to send, but edit_session is asynchronous, so I have to send to avoid blocking the main thread.
-        if let Some(tip_composition) = composition.tip_composition.clone() {
+        if let Some(tip_composition) = text_service.tip_composition()? {
             edit_session(
                 text_service.tid,
                 text_service.context()?,
@@ -2,20 +2,24 @@ use windows::{
     core::{IUnknown, Interface as _, BSTR, GUID, PCWSTR},
     Win32::{
         Foundation::{BOOL, E_INVALIDARG, POINT, RECT},
+        Graphics::Gdi::HBITMAP,
         System::Ole::CONNECT_E_CANNOTCONNECT,
         UI::{
             TextServices::{
                 ITfLangBarItemButton_Impl, ITfLangBarItemSink, ITfLangBarItem_Impl, ITfMenu,
                 ITfSource_Impl, TfLBIClick, GUID_LBI_INPUTMODE, TF_LANGBARITEMINFO,
-                TF_LBI_STYLE_BTN_BUTTON,
+                TF_LBI_STYLE_BTN_BUTTON, TF_LBI_STYLE_BTN_MENU, TF_LBMENUF_CHECKED,
             },
             WindowsAndMessaging::{LoadImageW, HICON, IMAGE_ICON, LR_DEFAULTCOLOR},
         },
     },
 };
 
+use ime_engine::input_mode::InputMode;
+
 use crate::{
-    engine::{input_mode::InputMode, state::IMEState},
+    engine::state::IMEState,
+    extension::StringExt as _,
     globals::{DllModule, GUID_TEXT_SERVICE, TEXTSERVICE_LANGBARITEMSINK_COOKIE},
 };
 
@@ -26,7 +30,8 @@ use super::factory::TextServiceFactory_Impl;
 const INFO: TF_LANGBARITEMINFO = TF_LANGBARITEMINFO {
     clsidService: GUID_TEXT_SERVICE,
     guidItem: GUID_LBI_INPUTMODE,
-    dwStyle: TF_LBI_STYLE_BTN_BUTTON,
+    // clicking the button toggles the mode, and the arrow opens the menu of the modes
+    dwStyle: TF_LBI_STYLE_BTN_BUTTON | TF_LBI_STYLE_BTN_MENU,
     ulSort: 0,
     szDescription: [0; 32],
 };
@@ -63,28 +68,48 @@ impl ITfLangBarItem_Impl for TextServiceFactory_Impl {
 impl ITfLangBarItemButton_Impl for TextServiceFactory_Impl {
     #[macros::anyhow]
     fn OnClick(&self, _click: TfLBIClick, _pt: &POINT, _prcarea: *const RECT) -> Result<()> {
-        let mode = {
-            let ime_mode = &IMEState::get()?.input_mode;
-            match ime_mode {
-                InputMode::Latin => InputMode::Kana,
-                InputMode::Kana => InputMode::Latin,
-            }
-        };
+        let mode = IMEState::get()?.input_mode.toggled();
 
         self.set_input_mode(mode)?;
 
         Ok(())
     }
 
-    // this method should not be called
+    // the menu item id is the index of the mode in InputMode::ALL
     #[macros::anyhow]
-    fn InitMenu(&self, _pmenu: Option<&ITfMenu>) -> Result<()> {
+    fn InitMenu(&self, pmenu: Option<&ITfMenu>) -> Result<()> {
+        let Some(menu) = pmenu else {
+            return Ok(());
+        };
+        let current = IMEState::get()?.input_mode.clone();
+
+        for (id, mode) in InputMode::ALL.iter().enumerate() {
+            let flags = if *mode == current {
+                TF_LBMENUF_CHECKED
+            } else {
+                0
+            };
+            unsafe {
+                menu.AddMenuItem(
+                    id as u32,
+                    flags,
+                    HBITMAP::default(),
+                    HBITMAP::default(),
+                    &mode.label().to_wide_16_unpadded(),
+                    std::ptr::null_mut(),
+                )?;
+            }
+        }
+
         Ok(())
     }
 
-    // this method should not be called
     #[macros::anyhow]
-    fn OnMenuSelect(&self, _w_id: u32) -> Result<()> {
+    fn OnMenuSelect(&self, w_id: u32) -> Result<()> {
+        if let Some(mode) = InputMode::ALL.get(w_id as usize) {
+            self.set_input_mode(mode.clone())?;
+        }
+
         Ok(())
     }
 
@@ -94,8 +119,8 @@ impl ITfLangBarItemButton_Impl for TextServiceFactory_Impl {
         let input_mode = &IMEState::get()?.input_mode;
 
         let icon_id = match input_mode {
-            InputMode::Kana => 102,
-            InputMode::Latin => 103,
+            InputMode::Direct => 103,
+            _ => 102,
         };
 
         unsafe {
@@ -1,5 +1,7 @@
 use std::collections::HashMap;
 
+use ime_engine::{keymap::Keymap, roman2kana::Layout, settings::Settings};
+
 use crate::{engine::state::IMEState, globals::GUID_DISPLAY_ATTRIBUTE};
 
 use super::factory::TextServiceFactory_Impl;
@@ -25,6 +27,16 @@ impl ITfTextInputProcessor_Impl for TextServiceFactory_Impl {
         let mut text_service = self.borrow_mut()?;
 
         text_service.tid = tid;
+
+        // reload settings, keymap and romaji table, so that changes take effect by switching the
+        // input method
+        {
+            let mut state = IMEState::get()?;
+            state.settings = Settings::load();
+            state.keymap = Keymap::load();
+            text_service.borrow_mut_composition()?.layout = Layout::load(&state.settings);
+        }
+
         let thread_mgr = ptim.context("Thread manager is null")?;
         text_service.thread_mgr = Some(thread_mgr.clone());
 
@@ -94,11 +106,7 @@ impl ITfTextInputProcessor_Impl for TextServiceFactory_Impl {
 
             // end composition
             self.end_composition()?;
-            let mut composition = text_service.borrow_mut_composition()?;
-
-            if composition.tip_composition.is_some() {
-                composition.tip_composition = None;
-            }
+            text_service.set_tip_composition(None)?;
 
             // remove key event sink
             log::debug!("UnadviseKeyEventSink");
@@ -142,6 +150,11 @@ impl ITfTextInputProcessor_Impl for TextServiceFactory_Impl {
         // clear display attribute
         text_service.display_attribute_atom.clear();
 
+        // close the sessions of all contexts
+        if let Err(e) = IMEState::get()?.ipc_service.release_all_contexts() {
+            log::warn!("Failed to close sessions: {:#}", e);
+        }
+
         text_service.tid = 0;
         text_service.thread_mgr = None;
 
@@ -5,12 +5,12 @@ use std::{
 
 use windows::{
     core::{Interface, GUID},
-    Win32::UI::TextServices::{ITfContext, ITfTextInputProcessor, ITfThreadMgr},
+    Win32::UI::TextServices::{ITfComposition, ITfContext, ITfTextInputProcessor, ITfThreadMgr},
 };
 
 use anyhow::{Context, Result};
 
-use crate::engine::{composition::Composition, input_mode::InputMode};
+use ime_engine::{composition::Composition, input_mode::InputMode};
 
 #[derive(Default, Debug)]
 pub struct TextService {
@@ -18,6 +18,7 @@ pub struct TextService {
     pub thread_mgr: Option<ITfThreadMgr>,
     pub context: Option<ITfContext>,
     pub composition: RefCell<Composition>,
+    pub tip_composition: RefCell<Option<ITfComposition>>,
     pub display_attribute_atom: HashMap<GUID, u32>,
     pub mode: InputMode,
     pub this: Option<ITfTextInputProcessor>,
@@ -48,4 +49,14 @@ impl TextService {
     pub fn borrow_mut_composition(&self) -> Result<RefMut<Composition>> {
         Ok(self.composition.try_borrow_mut()?)
     }
+
+    // the composition of tsf, which is kept apart from the state of ime-engine
+    pub fn tip_composition(&self) -> Result<Option<ITfComposition>> {
+        Ok(self.tip_composition.try_borrow()?.clone())
+    }
+
+    pub fn set_tip_composition(&self, composition: Option<ITfComposition>) -> Result<()> {
+        *self.tip_composition.try_borrow_mut()? = composition;
+        Ok(())
+    }
 }
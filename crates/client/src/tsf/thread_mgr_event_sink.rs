@@ -2,7 +2,9 @@ use windows::Win32::UI::TextServices::{ITfContext, ITfDocumentMgr, ITfThreadMgrE
 
 use anyhow::Result;
 
-use crate::engine::{client_action::ClientAction, composition::CompositionState};
+use ime_engine::{client_action::ClientAction, composition::CompositionState};
+
+use crate::engine::state::IMEState;
 
 use super::factory::TextServiceFactory_Impl;
 
@@ -33,6 +35,8 @@ impl ITfThreadMgrEventSink_Impl for TextServiceFactory_Impl {
 
         let actions = vec![ClientAction::EndComposition];
         self.handle_action(&actions, CompositionState::None)?;
+        // the text committed in the other context is not before the caret
+        self.forget_commit()?;
 
         Ok(())
     }
@@ -43,7 +47,12 @@ impl ITfThreadMgrEventSink_Impl for TextServiceFactory_Impl {
     }
 
     #[macros::anyhow]
-    fn OnPopContext(&self, _pic: Option<&ITfContext>) -> Result<()> {
+    fn OnPopContext(&self, pic: Option<&ITfContext>) -> Result<()> {
+        // the context is destroyed, so its session is no longer needed
+        if let Some(context) = pic {
+            IMEState::get()?.ipc_service.release_context(context)?;
+        }
+
         Ok(())
     }
 }
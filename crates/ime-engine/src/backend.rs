@@ -0,0 +1,86 @@
+use protos::proto::{action::Kind, CandidateSource, CompositionSnapshot, Suggestion};
+
+use anyhow::Result;
+
+// kkc server, which owns the composing text and updates the candidate window by itself
+pub trait Backend {
+    // apply actions in one round trip, and return the resulting composition
+    fn process_key(&mut self, actions: Vec<Kind>) -> Result<Snapshot>;
+
+    // replace the composing text with the reading of the committed text, for the reconversion
+    fn reverse_lookup(&mut self, text: &str) -> Result<Snapshot>;
+}
+
+// a conversion candidate returned by kkc server
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Candidate {
+    // stable across sessions, used to refer to the candidate later
+    pub id: u64,
+    pub text: String,
+    // rest of the spell which is not converted by this candidate
+    pub sub_text: String,
+    pub reading: String,
+    pub annotation: Option<String>,
+    pub source: CandidateSource,
+    pub corresponding_count: i32,
+}
+
+impl From<Suggestion> for Candidate {
+    fn from(suggestion: Suggestion) -> Self {
+        Candidate {
+            id: suggestion.id,
+            source: suggestion.source(),
+            text: suggestion.text,
+            sub_text: suggestion.subtext,
+            reading: suggestion.reading,
+            annotation: Some(suggestion.annotation).filter(|annotation| !annotation.is_empty()),
+            corresponding_count: suggestion.corresponding_count,
+        }
+    }
+}
+
+// composition after a batch of actions is applied on kkc server
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    pub spell: String,
+    pub preview: String,
+    pub suffix: String,
+    pub cursor: i32,
+    pub candidates: Vec<Candidate>,
+    pub selection: i32,
+    pub segments: Vec<Segment>,
+    pub focused_segment: i32,
+}
+
+impl From<CompositionSnapshot> for Snapshot {
+    fn from(snapshot: CompositionSnapshot) -> Self {
+        Snapshot {
+            candidates: snapshot
+                .suggestions
+                .into_iter()
+                .map(Candidate::from)
+                .collect(),
+            spell: snapshot.spell,
+            preview: snapshot.preview,
+            suffix: snapshot.suffix,
+            cursor: snapshot.cursor,
+            selection: snapshot.selection,
+            segments: snapshot
+                .segments
+                .into_iter()
+                .map(|segment| Segment {
+                    reading: segment.reading,
+                    text: segment.text,
+                })
+                .collect(),
+            focused_segment: snapshot.focused_segment,
+        }
+    }
+}
+
+// a clause of the spell which is converted separately
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Segment {
+    pub reading: String,
+    pub text: String,
+}
@@ -0,0 +1,45 @@
+use super::input_mode::InputMode;
+
+#[derive(Debug, PartialEq)]
+pub enum ClientAction {
+    StartComposition,
+    EndComposition,
+    // the text committed last is taken back into the composition
+    UndoCommit,
+    // the selected text is converted again
+    Reconvert,
+
+    AppendText(String),
+    RemoveText,
+
+    MoveCursor(i32),
+    SetSelection(SetSelectionType),
+
+    SetIMEMode(InputMode),
+
+    ShrinkText,
+
+    ConvertScript(Script),
+
+    ResizeSegment(i32),
+    MoveSegment(i32),
+}
+
+// F6-F10 conversions of the reading
+#[derive(Debug, PartialEq)]
+pub enum Script {
+    Hiragana,
+    Katakana,
+    HalfKatakana,
+    FullAlphanumeric,
+    HalfAlphanumeric,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SetSelectionType {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Number(i32),
+}
@@ -0,0 +1,631 @@
+use super::{
+    backend::{Backend, Candidate, Segment, Snapshot},
+    client_action::{ClientAction, Script, SetSelectionType},
+    input_mode::InputMode,
+    kana_input,
+    roman2kana::{Layout, Replace},
+    settings::{ConversionStyle, Settings},
+    text_sink::TextSink,
+    user_action::{Function, Navigation, UserAction},
+};
+
+use anyhow::Result;
+use protos::proto::{
+    self,
+    action::{ClearText, Kind, RemoveText},
+};
+
+#[derive(Default, Clone, PartialEq, Debug)]
+pub enum CompositionState {
+    #[default]
+    None,
+    Composing,
+    Previewing,
+    Selecting,
+}
+
+#[derive(Default, Clone, Debug)]
+pub struct Composition {
+    pub spell: String,            // hiragana typed by the user
+    pub cursor: i32,              // caret position in the spell, counted in characters
+    pub preview: String,          // text to be previewed
+    pub suffix: String,           // text to be appended after preview
+    pub corresponding_count: i32, // corresponding count of the preview
+    pub suggestions: Vec<String>,
+    pub selection_index: i32,
+    pub candidates: Vec<Candidate>,
+    pub segments: Vec<Segment>, // empty unless the user resized the segments
+    pub focused_segment: i32,
+    pub state: CompositionState,
+    pub last_commit: Option<CommittedText>, // cleared by the next key, and by forget_commit
+    pub layout: Option<Layout>,             // None in the kana input style
+}
+
+// what is needed to take a commit back into the composition
+#[derive(Clone, Debug)]
+pub struct CommittedText {
+    pub spell: String,
+    pub text: String,
+    pub selection_index: i32,
+}
+
+impl Composition {
+    // true if the caret is not at the end of the spell
+    pub fn is_editing(&self) -> bool {
+        (self.cursor as usize) < self.spell.chars().count()
+    }
+
+    // conversions apply to the whole spell, so the caret goes back to the end first
+    fn at_end(&self, action: ClientAction) -> Vec<ClientAction> {
+        let mut actions = vec![];
+        if self.is_editing() {
+            let offset = self.spell.chars().count() as i32 - self.cursor;
+            actions.push(ClientAction::MoveCursor(offset));
+        }
+        actions.push(action);
+        actions
+    }
+
+    fn select(&self, selection: SetSelectionType) -> Vec<ClientAction> {
+        self.at_end(ClientAction::SetSelection(selection))
+    }
+
+    // index of the n-th (1-9) candidate on the current page
+    fn candidate_on_page(&self, number: i32, page_size: i32) -> Option<i32> {
+        if !(1..=page_size.min(9)).contains(&number) {
+            return None;
+        }
+
+        let index = self.selection_index / page_size * page_size + number - 1;
+        ((index as usize) < self.candidates.len()).then_some(index)
+    }
+
+    // the kana before the caret with the dakuten or the handakuten of the kana input style
+    // the spell is up to date here, as a key appends no more than a character
+    fn combine(&self, text: &str) -> Option<char> {
+        let mut chars = text.chars();
+        let (Some(mark), None) = (chars.next(), chars.next()) else {
+            return None;
+        };
+        let cursor = (self.cursor as usize).checked_sub(1)?;
+        let kana = self.spell.chars().nth(cursor)?;
+
+        kana_input::combine(kana, mark)
+    }
+
+    fn clear(&mut self) {
+        self.spell.clear();
+        self.segments.clear();
+        self.focused_segment = 0;
+        self.cursor = 0;
+        self.selection_index = 0;
+        self.corresponding_count = 0;
+        self.preview.clear();
+        self.suffix.clear();
+    }
+}
+
+// the romaji sent to kkc server is replaced by the kana of the layout
+fn replace_text(pending: &mut Vec<Kind>, mode: &InputMode, replace: Replace) {
+    for _ in 0..replace.remove {
+        pending.push(Kind::RemoveText(RemoveText {}));
+    }
+    if !replace.text.is_empty() {
+        pending.push(Kind::AppendText(mode.transform(&replace.text)));
+    }
+}
+
+// commit the preview, the rest of the spell is left in the composition
+fn commit(suffix: &str) -> (CompositionState, Vec<ClientAction>) {
+    if suffix.is_empty() {
+        (CompositionState::None, vec![ClientAction::EndComposition])
+    } else {
+        #[cfg(not(target_arch = "x86"))]
+        {
+            (CompositionState::Composing, vec![ClientAction::ShrinkText])
+        }
+
+        // on x86 application, we can't set_text on the same time
+        #[cfg(target_arch = "x86")]
+        {
+            (CompositionState::None, vec![ClientAction::EndComposition])
+        }
+    }
+}
+
+fn proto_script(script: &Script) -> proto::Script {
+    match script {
+        Script::Hiragana => proto::Script::Hiragana,
+        Script::Katakana => proto::Script::Katakana,
+        Script::HalfKatakana => proto::Script::HalfKatakana,
+        Script::FullAlphanumeric => proto::Script::FullAlphanumeric,
+        Script::HalfAlphanumeric => proto::Script::HalfAlphanumeric,
+    }
+}
+
+impl Composition {
+    // decide what the key does in the current state, None if the key is not for the ime
+    pub fn transition(
+        &self,
+        action: &UserAction,
+        mode: &InputMode,
+        settings: &Settings,
+    ) -> Option<(CompositionState, Vec<ClientAction>)> {
+        let page_size = settings.page_size as i32;
+        // in the explicit style, the candidates are hidden until the conversion is started
+        // the modes with their own script are not converted while typing either
+        let explicit =
+            settings.conversion_style == ConversionStyle::Explicit || mode.script().is_some();
+
+        let transition = match self.state {
+            CompositionState::None => match action {
+                UserAction::Input(char) if !mode.is_direct() => (
+                    CompositionState::Composing,
+                    vec![
+                        ClientAction::StartComposition,
+                        ClientAction::AppendText(char.to_string()),
+                    ],
+                ),
+                UserAction::Number(number) if !mode.is_direct() => (
+                    CompositionState::Composing,
+                    vec![
+                        ClientAction::StartComposition,
+                        ClientAction::AppendText(number.to_string()),
+                    ],
+                ),
+                UserAction::ToggleInputMode => (
+                    CompositionState::None,
+                    vec![ClientAction::SetIMEMode(mode.toggled())],
+                ),
+                // right after a commit, the reading and the candidates come back
+                // the modes with their own script show the reading in it instead
+                UserAction::UndoCommit if self.last_commit.is_some() && !mode.is_direct() => (
+                    match mode.script() {
+                        Some(_) => CompositionState::Composing,
+                        None => CompositionState::Selecting,
+                    },
+                    vec![ClientAction::UndoCommit],
+                ),
+                // the selection and its reading are only known to the sink and kkc server, so
+                // handle_action gives the key back to the application if either is missing
+                UserAction::Reconvert if !mode.is_direct() => {
+                    (CompositionState::Selecting, vec![ClientAction::Reconvert])
+                }
+                _ => return None,
+            },
+            CompositionState::Composing | CompositionState::Selecting => match action {
+                // while the candidate list is open, the number keys pick a candidate on the page
+                UserAction::Number(number) if self.state == CompositionState::Selecting => {
+                    match self.candidate_on_page(*number as i32, page_size) {
+                        // each segment has its own candidates, so picking one doesn't commit
+                        Some(index) if !self.segments.is_empty() => (
+                            CompositionState::Selecting,
+                            vec![ClientAction::SetSelection(SetSelectionType::Number(index))],
+                        ),
+                        Some(index) => {
+                            let suffix = &self.candidates[index as usize].sub_text;
+                            let (transition, commit) = commit(suffix);
+                            let mut actions =
+                                vec![ClientAction::SetSelection(SetSelectionType::Number(index))];
+                            actions.extend(commit);
+                            (transition, actions)
+                        }
+                        None => (CompositionState::Selecting, vec![]),
+                    }
+                }
+                // typing commits the selected candidate, and starts the next composition
+                UserAction::Input(char) if self.state == CompositionState::Selecting => (
+                    CompositionState::Composing,
+                    vec![
+                        ClientAction::EndComposition,
+                        ClientAction::StartComposition,
+                        ClientAction::AppendText(char.to_string()),
+                    ],
+                ),
+                // close the candidate list, and go back to the first candidate
+                UserAction::Escape if self.state == CompositionState::Selecting => (
+                    CompositionState::Composing,
+                    if self.segments.is_empty() && !explicit {
+                        vec![ClientAction::SetSelection(SetSelectionType::Number(0))]
+                    } else {
+                        // any edit of the spell discards the segments and the conversion
+                        vec![ClientAction::MoveCursor(0)]
+                    },
+                ),
+                // move the focus between segments
+                UserAction::Navigation(Navigation::Left) if !self.segments.is_empty() => (
+                    CompositionState::Selecting,
+                    vec![ClientAction::MoveSegment(-1)],
+                ),
+                UserAction::Navigation(Navigation::Right) if !self.segments.is_empty() => (
+                    CompositionState::Selecting,
+                    vec![ClientAction::MoveSegment(1)],
+                ),
+                // the first key of the conversion shows the first candidate
+                UserAction::Space
+                | UserAction::ShiftSpace
+                | UserAction::Navigation(
+                    Navigation::Up | Navigation::Down | Navigation::PageUp | Navigation::PageDown,
+                ) if explicit && self.state == CompositionState::Composing => (
+                    CompositionState::Selecting,
+                    self.select(SetSelectionType::Number(0)),
+                ),
+                UserAction::ShiftLeft => (
+                    CompositionState::Selecting,
+                    self.at_end(ClientAction::ResizeSegment(-1)),
+                ),
+                UserAction::ShiftRight => (
+                    CompositionState::Selecting,
+                    self.at_end(ClientAction::ResizeSegment(1)),
+                ),
+                UserAction::Input(char) => (
+                    CompositionState::Composing,
+                    vec![ClientAction::AppendText(char.to_string())],
+                ),
+                UserAction::Number(number) => (
+                    CompositionState::Composing,
+                    vec![ClientAction::AppendText(number.to_string())],
+                ),
+                UserAction::Backspace => {
+                    // the last character before the caret is removed
+                    if self.spell.chars().count() == 1 && self.cursor == 1 {
+                        (
+                            CompositionState::None,
+                            vec![ClientAction::RemoveText, ClientAction::EndComposition],
+                        )
+                    } else {
+                        (CompositionState::Composing, vec![ClientAction::RemoveText])
+                    }
+                }
+                // the character after the caret is removed
+                UserAction::Delete if !self.is_editing() => (self.state.clone(), vec![]),
+                UserAction::Delete => {
+                    if self.spell.chars().count() == 1 {
+                        (
+                            CompositionState::None,
+                            vec![
+                                ClientAction::MoveCursor(1),
+                                ClientAction::RemoveText,
+                                ClientAction::EndComposition,
+                            ],
+                        )
+                    } else {
+                        (
+                            CompositionState::Composing,
+                            vec![ClientAction::MoveCursor(1), ClientAction::RemoveText],
+                        )
+                    }
+                }
+                UserAction::Enter => commit(&self.suffix),
+                UserAction::Escape => (
+                    CompositionState::None,
+                    vec![ClientAction::RemoveText, ClientAction::EndComposition],
+                ),
+                UserAction::Navigation(direction) => match direction {
+                    Navigation::Right => (
+                        CompositionState::Composing,
+                        vec![ClientAction::MoveCursor(1)],
+                    ),
+                    Navigation::Left => (
+                        CompositionState::Composing,
+                        vec![ClientAction::MoveCursor(-1)],
+                    ),
+                    Navigation::Up => (
+                        CompositionState::Selecting,
+                        self.select(SetSelectionType::Up),
+                    ),
+                    Navigation::Down => (
+                        CompositionState::Selecting,
+                        self.select(SetSelectionType::Down),
+                    ),
+                    Navigation::PageUp => (
+                        CompositionState::Selecting,
+                        self.select(SetSelectionType::PageUp),
+                    ),
+                    Navigation::PageDown => (
+                        CompositionState::Selecting,
+                        self.select(SetSelectionType::PageDown),
+                    ),
+                    Navigation::Home => (
+                        CompositionState::Composing,
+                        vec![ClientAction::MoveCursor(-self.cursor)],
+                    ),
+                    Navigation::End => (
+                        CompositionState::Composing,
+                        vec![ClientAction::MoveCursor(
+                            self.spell.chars().count() as i32 - self.cursor,
+                        )],
+                    ),
+                },
+                UserAction::ToggleInputMode => (
+                    CompositionState::None,
+                    vec![ClientAction::SetIMEMode(mode.toggled())],
+                ),
+                UserAction::Space => (
+                    CompositionState::Selecting,
+                    self.select(SetSelectionType::Down),
+                ),
+                UserAction::ShiftSpace => (
+                    CompositionState::Selecting,
+                    self.select(SetSelectionType::Up),
+                ),
+                UserAction::Function(function) => (
+                    CompositionState::Composing,
+                    self.at_end(ClientAction::ConvertScript(match function {
+                        Function::Six => Script::Hiragana,
+                        Function::Seven => Script::Katakana,
+                        Function::Eight => Script::HalfKatakana,
+                        Function::Nine => Script::FullAlphanumeric,
+                        Function::Ten => Script::HalfAlphanumeric,
+                    })),
+                ),
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        Some(transition)
+    }
+
+    // the document may be changed out of sight of the engine, so the commit can't be taken back
+    // called when a key is passed to the application, the caret is moved or the focus changes
+    pub fn forget_commit(&mut self) {
+        self.last_commit = None;
+    }
+
+    // perform the actions on the sink and kkc server, then move to the next state
+    // false if the key turned out to have nothing to do, the host passes it to the application
+    pub fn handle_action(
+        &mut self,
+        actions: &[ClientAction],
+        transition: CompositionState,
+        mode: &InputMode,
+        sink: &mut impl TextSink,
+        backend: &mut impl Backend,
+    ) -> Result<bool> {
+        let mut transition = transition;
+        let mut applied = true;
+        // actions for kkc server are sent together in a single request
+        let mut pending: Vec<Kind> = vec![];
+        // only the very next key can undo the commit
+        let mut last_commit = self.last_commit.take();
+
+        for action in actions {
+            // the pending romaji is resolved before anything but typing, so nothing is left
+            // pending when the cursor moves, the text is committed, undone or reconverted
+            if !matches!(
+                action,
+                ClientAction::AppendText(_) | ClientAction::RemoveText
+            ) {
+                if let Some(replace) = self.layout.as_mut().and_then(|layout| layout.flush()) {
+                    replace_text(&mut pending, mode, replace);
+                }
+            }
+
+            match action {
+                ClientAction::StartComposition => {
+                    sink.start_composition()?;
+                }
+                ClientAction::EndComposition => {
+                    self.end_composition(sink, backend, mode, &mut pending)?;
+                }
+                ClientAction::UndoCommit => {
+                    let Some(commit) = last_commit.take() else {
+                        applied = false;
+                        continue;
+                    };
+                    if !sink.start_composition_over(&commit.text)? {
+                        log::warn!("Committed text {:?} is not before the caret", commit.text);
+                        applied = false;
+                        continue;
+                    }
+
+                    // the committed text is replaced by the reading when the preview is set
+                    pending.push(Kind::AppendText(commit.spell));
+                    if mode.script().is_none() {
+                        pending.push(Kind::SelectSuggestion(commit.selection_index));
+                    }
+                }
+                ClientAction::Reconvert => {
+                    let text = sink.selected_text()?;
+                    if text.is_empty() {
+                        applied = false;
+                        continue;
+                    }
+
+                    self.flush(sink, backend, mode, &mut pending)?;
+                    // the selection is left in the document unless its reading is known
+                    let snapshot = match backend.reverse_lookup(&text) {
+                        Ok(snapshot) => snapshot,
+                        Err(e) => {
+                            log::warn!("Failed to reconvert {text:?}: {e:#}");
+                            applied = false;
+                            continue;
+                        }
+                    };
+                    sink.start_composition_over_selection()?;
+                    self.apply_snapshot(sink, snapshot, true)?;
+                }
+                ClientAction::AppendText(text) => {
+                    if let Some(kana) = self.combine(text).filter(|_| mode.is_kana()) {
+                        pending.push(Kind::RemoveText(RemoveText {}));
+                        pending.push(Kind::AppendText(kana.to_string()));
+                        continue;
+                    }
+
+                    match self.layout.as_mut().filter(|_| mode.is_kana()) {
+                        Some(layout) => replace_text(&mut pending, mode, layout.input(text)),
+                        None => pending.push(Kind::AppendText(mode.transform(text))),
+                    }
+                }
+                ClientAction::RemoveText => {
+                    if let Some(layout) = self.layout.as_mut() {
+                        layout.backspace();
+                    }
+                    pending.push(Kind::RemoveText(RemoveText {}));
+                }
+                ClientAction::MoveCursor(offset) => {
+                    pending.push(Kind::MoveCursor(*offset));
+                }
+                ClientAction::SetIMEMode(next) => {
+                    // the composition is committed in the current mode before it is changed
+                    self.end_composition(sink, backend, mode, &mut pending)?;
+                    sink.set_input_mode(next.clone())?;
+                }
+                ClientAction::SetSelection(selection) => {
+                    pending.push(match selection {
+                        SetSelectionType::Up => Kind::MoveSelection(-1),
+                        SetSelectionType::Down => Kind::MoveSelection(1),
+                        SetSelectionType::PageUp => Kind::MovePage(-1),
+                        SetSelectionType::PageDown => Kind::MovePage(1),
+                        SetSelectionType::Number(number) => Kind::SelectSuggestion(*number),
+                    });
+                }
+                ClientAction::ConvertScript(script) => {
+                    pending.push(Kind::ConvertScript(proto_script(script) as i32));
+                }
+                ClientAction::ResizeSegment(offset) => {
+                    pending.push(Kind::ResizeSegment(*offset));
+                }
+                ClientAction::MoveSegment(offset) => {
+                    pending.push(Kind::MoveSegment(*offset));
+                }
+                ClientAction::ShrinkText => {
+                    // the preview has to be up to date before it is committed
+                    self.flush(sink, backend, mode, &mut pending)?;
+
+                    // first, end composition
+                    sink.set_text(&self.preview, "")?;
+                    sink.end_composition()?;
+
+                    // then, start composition
+                    sink.start_composition()?;
+
+                    // shrink text
+                    pending.push(Kind::ShrinkText(self.corresponding_count));
+
+                    transition = CompositionState::Composing;
+                }
+            }
+        }
+
+        self.flush(sink, backend, mode, &mut pending)?;
+
+        if transition != CompositionState::None && self.preview.is_empty() {
+            transition = CompositionState::None;
+        }
+        self.state = transition;
+
+        Ok(applied)
+    }
+
+    // commit the composition, which the next key can take back
+    fn end_composition(
+        &mut self,
+        sink: &mut impl TextSink,
+        backend: &mut impl Backend,
+        mode: &InputMode,
+        pending: &mut Vec<Kind>,
+    ) -> Result<()> {
+        self.flush(sink, backend, mode, pending)?;
+        sink.set_text(&self.preview, &self.suffix)?;
+        sink.end_composition()?;
+
+        let text = format!("{}{}", self.preview, self.suffix);
+        if !text.is_empty() {
+            self.last_commit = Some(CommittedText {
+                spell: self.spell.clone(),
+                text,
+                selection_index: self.selection_index,
+            });
+        }
+
+        self.clear();
+        pending.push(Kind::ClearText(ClearText {}));
+
+        Ok(())
+    }
+
+    // send pending actions to kkc server, and apply the resulting snapshot to the composition
+    fn flush(
+        &mut self,
+        sink: &mut impl TextSink,
+        backend: &mut impl Backend,
+        mode: &InputMode,
+        pending: &mut Vec<Kind>,
+    ) -> Result<()> {
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        // the text is already committed or discarded when only clear is requested
+        let edited = pending
+            .iter()
+            .any(|kind| !matches!(kind, Kind::ClearText(_)));
+
+        // the mode is shared by the contexts while each has its own session, so it goes with
+        // every request, and kkc server shows the reading in its script until a selection action
+        let script = mode
+            .script()
+            .map_or(proto::Script::Unspecified, |script| proto_script(&script));
+        pending.insert(0, Kind::ModeScript(script as i32));
+
+        let snapshot = backend.process_key(std::mem::take(pending))?;
+        self.apply_snapshot(sink, snapshot, edited)
+    }
+
+    // take the composition from kkc server, and show it on the sink if the text is changed
+    fn apply_snapshot(
+        &mut self,
+        sink: &mut impl TextSink,
+        snapshot: Snapshot,
+        edited: bool,
+    ) -> Result<()> {
+        self.corresponding_count = snapshot
+            .candidates
+            .get(snapshot.selection as usize)
+            .map(|candidate| candidate.corresponding_count)
+            .unwrap_or(0);
+        self.preview = snapshot.preview;
+        self.suffix = snapshot.suffix;
+        self.selection_index = snapshot.selection;
+        self.candidates = snapshot.candidates;
+        self.spell = snapshot.spell;
+        self.cursor = snapshot.cursor;
+        self.segments = snapshot.segments;
+        self.focused_segment = snapshot.focused_segment;
+
+        // while the caret is inside the spell, show the spell itself so that the user can edit it
+        let editing = self.is_editing();
+        if editing {
+            self.preview = self.spell.clone();
+            self.suffix.clear();
+        }
+
+        if edited {
+            sink.set_text(&self.preview, &self.suffix)?;
+            if editing {
+                // tsf counts the position in utf-16
+                let position = self
+                    .spell
+                    .chars()
+                    .take(self.cursor as usize)
+                    .map(char::len_utf16)
+                    .sum::<usize>();
+                sink.set_cursor(position as i32)?;
+            } else if !self.segments.is_empty() {
+                // the caret shows the end of the focused segment
+                let position = self
+                    .segments
+                    .iter()
+                    .take(self.focused_segment as usize + 1)
+                    .flat_map(|segment| segment.text.chars())
+                    .map(char::len_utf16)
+                    .sum::<usize>();
+                sink.set_cursor(position as i32)?;
+            }
+        }
+
+        Ok(())
+    }
+}
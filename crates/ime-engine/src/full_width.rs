@@ -0,0 +1,168 @@
+// width conversion between ascii, half-width katakana and their full-width forms
+// https://www.unicode.org/charts/nameslist/n_FF00.html
+
+use super::kana_input::{combine, split};
+
+// full-width forms of U+FF61..=U+FF9F, in the order of the half-width katakana block
+const HALF_KATAKANA: &str = "。「」、・ヲァィゥェォャュョッーアイウエオカキクケコサシスセソタチツテトナニヌネノハヒフヘホマミムメモヤユヨラリルレロワン゛゜";
+
+// full-width ascii is U+FF01..=U+FF5E, in the same order as '!'..='~'
+const ASCII_OFFSET: u32 = 0xFEE0;
+
+// which classes of ascii are converted, the katakana is converted regardless
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Classes {
+    pub digits: bool,
+    pub letters: bool,
+    // the punctuation and the space, which becomes the ideographic space
+    pub symbols: bool,
+}
+
+impl Classes {
+    pub const ALL: Classes = Classes {
+        digits: true,
+        letters: true,
+        symbols: true,
+    };
+
+    // only the katakana
+    pub const NONE: Classes = Classes {
+        digits: false,
+        letters: false,
+        symbols: false,
+    };
+
+    // in azookey, fullwidth alphabet will not be processed
+    pub const SYMBOLS: Classes = Classes {
+        digits: false,
+        letters: false,
+        symbols: true,
+    };
+
+    pub fn contains(&self, c: char) -> bool {
+        if c.is_ascii_digit() {
+            self.digits
+        } else if c.is_ascii_alphabetic() {
+            self.letters
+        } else {
+            self.symbols
+        }
+    }
+}
+
+impl Default for Classes {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+fn full_ascii(c: char) -> Option<char> {
+    match c {
+        ' ' => Some('\u{3000}'),
+        '!'..='~' => char::from_u32(c as u32 + ASCII_OFFSET),
+        _ => None,
+    }
+}
+
+fn half_ascii(c: char) -> Option<char> {
+    match c {
+        '\u{3000}' => Some(' '),
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - ASCII_OFFSET),
+        _ => None,
+    }
+}
+
+fn full_katakana(c: char) -> Option<char> {
+    let index = (c as u32).checked_sub(0xFF61)?;
+    HALF_KATAKANA.chars().nth(index as usize)
+}
+
+fn half_katakana(c: char) -> Option<char> {
+    let index = HALF_KATAKANA.chars().position(|full| full == c)?;
+    char::from_u32(0xFF61 + index as u32)
+}
+
+pub fn to_full(s: &str, classes: Classes) -> String {
+    let mut full = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c.is_ascii() {
+            match full_ascii(c).filter(|_| classes.contains(c)) {
+                Some(wide) => full.push(wide),
+                None => full.push(c),
+            }
+            continue;
+        }
+
+        let Some(kana) = full_katakana(c) else {
+            full.push(c);
+            continue;
+        };
+
+        // the mark after a katakana goes onto it, e.g. "ｶﾞ" -> "ガ"
+        let marked = chars
+            .peek()
+            .and_then(|&mark| full_katakana(mark))
+            .and_then(|mark| combine(kana, mark));
+        match marked {
+            Some(marked) => {
+                chars.next();
+                full.push(marked);
+            }
+            None => full.push(kana),
+        }
+    }
+
+    full
+}
+
+pub fn to_half(s: &str, classes: Classes) -> String {
+    let mut half = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        if let Some(narrow) = half_ascii(c) {
+            half.push(if classes.contains(narrow) { narrow } else { c });
+            continue;
+        }
+
+        // the half-width katakana has no voiced letters, so the mark is written after it
+        let (kana, mark) = match split(c) {
+            Some((kana, mark)) if half_katakana(kana).is_some() => (kana, Some(mark)),
+            _ => (c, None),
+        };
+        match half_katakana(kana) {
+            Some(narrow) => {
+                half.push(narrow);
+                half.extend(mark.and_then(half_katakana));
+            }
+            None => half.push(c),
+        }
+    }
+
+    half
+}
+
+pub fn to_halfwidth(s: &str) -> String {
+    to_half(s, Classes::ALL)
+}
+
+// every printable ascii character, including the alphabet and the digits
+pub fn to_full_alphanumeric(s: &str) -> String {
+    to_full(s, Classes::ALL)
+}
+
+// the text typed in the kana modes, where the punctuation is the japanese one
+pub fn to_fullwidth(s: &str) -> String {
+    let s: String = s
+        .chars()
+        .map(|c| match c {
+            ',' => '、',
+            '-' => 'ー',
+            '.' => '。',
+            _ => c,
+        })
+        .collect();
+
+    to_full(&s, Classes::SYMBOLS)
+}
@@ -0,0 +1,80 @@
+use super::{
+    client_action::Script,
+    full_width::{to_full_alphanumeric, to_fullwidth},
+};
+
+#[derive(Default, Clone, PartialEq, Debug)]
+pub enum InputMode {
+    // keys are passed to the application as they are
+    #[default]
+    Direct,
+    Hiragana,
+    Katakana,
+    HalfKatakana,
+    FullAlphanumeric,
+}
+
+impl InputMode {
+    // every mode, in the order of the language bar menu
+    pub const ALL: [InputMode; 5] = [
+        InputMode::Hiragana,
+        InputMode::Katakana,
+        InputMode::HalfKatakana,
+        InputMode::FullAlphanumeric,
+        InputMode::Direct,
+    ];
+
+    pub fn is_direct(&self) -> bool {
+        *self == InputMode::Direct
+    }
+
+    // the romaji is converted to kana in these modes
+    pub fn is_kana(&self) -> bool {
+        matches!(
+            self,
+            InputMode::Hiragana | InputMode::Katakana | InputMode::HalfKatakana
+        )
+    }
+
+    // the zenkaku/hankaku key switches between the direct input and hiragana
+    pub fn toggled(&self) -> InputMode {
+        match self {
+            InputMode::Direct => InputMode::Hiragana,
+            _ => InputMode::Direct,
+        }
+    }
+
+    // text sent to kkc server for the typed key
+    pub fn transform(&self, text: &str) -> String {
+        match self {
+            InputMode::Direct => text.to_string(),
+            InputMode::Hiragana | InputMode::Katakana | InputMode::HalfKatakana => {
+                to_fullwidth(text)
+            }
+            // the typed text is kept, so that no roman2kana happens
+            InputMode::FullAlphanumeric => to_full_alphanumeric(text),
+        }
+    }
+
+    // the script which the composition is shown and committed in
+    // None for hiragana, which is converted to kanji as usual
+    pub fn script(&self) -> Option<Script> {
+        match self {
+            InputMode::Direct | InputMode::Hiragana => None,
+            InputMode::Katakana => Some(Script::Katakana),
+            InputMode::HalfKatakana => Some(Script::HalfKatakana),
+            // the spell is already full-width, so it is shown as it is
+            InputMode::FullAlphanumeric => Some(Script::Hiragana),
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            InputMode::Direct => "直接入力",
+            InputMode::Hiragana => "ひらがな",
+            InputMode::Katakana => "全角カタカナ",
+            InputMode::HalfKatakana => "半角カタカナ",
+            InputMode::FullAlphanumeric => "全角英数",
+        }
+    }
+}
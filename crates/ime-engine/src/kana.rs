@@ -0,0 +1,269 @@
+// script conversion of kana: hiragana, katakana, half-width katakana and romaji
+// the romaji is the reverse of roman2kana, spelled in the hepburn or the kunrei system
+
+use super::full_width::{to_full, to_half, Classes};
+
+// katakana is U+30A1..=U+30F6, in the same order as the hiragana U+3041..=U+3096
+const KATAKANA_OFFSET: u32 = 0x60;
+
+pub fn to_katakana(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            // ぁ..=ゖ and ゝゞ
+            '\u{3041}'..='\u{3096}' | '\u{309D}'..='\u{309E}' => {
+                char::from_u32(c as u32 + KATAKANA_OFFSET).unwrap_or(c)
+            }
+            _ => c,
+        })
+        .collect()
+}
+
+// the half-width katakana is widened first, e.g. "ｶﾞ" -> "が"
+pub fn to_hiragana(s: &str) -> String {
+    to_full(s, Classes::NONE)
+        .chars()
+        .map(|c| match c {
+            // ァ..=ヶ and ヽヾ
+            '\u{30A1}'..='\u{30F6}' | '\u{30FD}'..='\u{30FE}' => {
+                char::from_u32(c as u32 - KATAKANA_OFFSET).unwrap_or(c)
+            }
+            _ => c,
+        })
+        .collect()
+}
+
+// whether the text is only made of kana and the prolonged sound mark, in any script
+pub fn is_kana(s: &str) -> bool {
+    !s.is_empty()
+        && to_hiragana(s)
+            .chars()
+            .all(|c| matches!(c, '\u{3041}'..='\u{3096}' | '\u{309D}'..='\u{309E}' | 'ー'))
+}
+
+pub fn to_half_katakana(s: &str) -> String {
+    to_half(&to_katakana(s), Classes::NONE)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Romanization {
+    // し shi, ち chi, つ tsu, ふ fu, じゃ ja
+    #[default]
+    Hepburn,
+    // し si, ち ti, つ tu, ふ hu, じゃ zya
+    Kunrei,
+}
+
+// (kana, hepburn, kunrei)
+const SYLLABLES: [(char, &str, &str); 86] = [
+    ('あ', "a", "a"),
+    ('い', "i", "i"),
+    ('う', "u", "u"),
+    ('え', "e", "e"),
+    ('お', "o", "o"),
+    ('か', "ka", "ka"),
+    ('き', "ki", "ki"),
+    ('く', "ku", "ku"),
+    ('け', "ke", "ke"),
+    ('こ', "ko", "ko"),
+    ('さ', "sa", "sa"),
+    ('し', "shi", "si"),
+    ('す', "su", "su"),
+    ('せ', "se", "se"),
+    ('そ', "so", "so"),
+    ('た', "ta", "ta"),
+    ('ち', "chi", "ti"),
+    ('つ', "tsu", "tu"),
+    ('て', "te", "te"),
+    ('と', "to", "to"),
+    ('な', "na", "na"),
+    ('に', "ni", "ni"),
+    ('ぬ', "nu", "nu"),
+    ('ね', "ne", "ne"),
+    ('の', "no", "no"),
+    ('は', "ha", "ha"),
+    ('ひ', "hi", "hi"),
+    ('ふ', "fu", "hu"),
+    ('へ', "he", "he"),
+    ('ほ', "ho", "ho"),
+    ('ま', "ma", "ma"),
+    ('み', "mi", "mi"),
+    ('む', "mu", "mu"),
+    ('め', "me", "me"),
+    ('も', "mo", "mo"),
+    ('や', "ya", "ya"),
+    ('ゆ', "yu", "yu"),
+    ('よ', "yo", "yo"),
+    ('ら', "ra", "ra"),
+    ('り', "ri", "ri"),
+    ('る', "ru", "ru"),
+    ('れ', "re", "re"),
+    ('ろ', "ro", "ro"),
+    ('わ', "wa", "wa"),
+    ('ゐ', "i", "i"),
+    ('ゑ', "e", "e"),
+    ('を', "wo", "wo"),
+    ('が', "ga", "ga"),
+    ('ぎ', "gi", "gi"),
+    ('ぐ', "gu", "gu"),
+    ('げ', "ge", "ge"),
+    ('ご', "go", "go"),
+    ('ざ', "za", "za"),
+    ('じ', "ji", "zi"),
+    ('ず', "zu", "zu"),
+    ('ぜ', "ze", "ze"),
+    ('ぞ', "zo", "zo"),
+    ('だ', "da", "da"),
+    ('ぢ', "ji", "zi"),
+    ('づ', "zu", "zu"),
+    ('で', "de", "de"),
+    ('ど', "do", "do"),
+    ('ば', "ba", "ba"),
+    ('び', "bi", "bi"),
+    ('ぶ', "bu", "bu"),
+    ('べ', "be", "be"),
+    ('ぼ', "bo", "bo"),
+    ('ぱ', "pa", "pa"),
+    ('ぴ', "pi", "pi"),
+    ('ぷ', "pu", "pu"),
+    ('ぺ', "pe", "pe"),
+    ('ぽ', "po", "po"),
+    ('ゔ', "vu", "vu"),
+    // the small kana which is not a part of a syllable is spelled as it is typed
+    ('ぁ', "xa", "xa"),
+    ('ぃ', "xi", "xi"),
+    ('ぅ', "xu", "xu"),
+    ('ぇ', "xe", "xe"),
+    ('ぉ', "xo", "xo"),
+    ('ゃ', "xya", "xya"),
+    ('ゅ', "xyu", "xyu"),
+    ('ょ', "xyo", "xyo"),
+    ('ゎ', "xwa", "xwa"),
+    ('ゕ', "xka", "xka"),
+    ('ゖ', "xke", "xke"),
+    ('っ', "xtsu", "xtu"),
+    ('ん', "n", "n"),
+];
+
+// syllables of the loanwords, which only the hepburn system spells
+const LOANWORDS: [(&str, &str); 22] = [
+    ("ふぁ", "fa"),
+    ("ふぃ", "fi"),
+    ("ふぇ", "fe"),
+    ("ふぉ", "fo"),
+    ("しぇ", "she"),
+    ("ちぇ", "che"),
+    ("じぇ", "je"),
+    ("てぃ", "ti"),
+    ("でぃ", "di"),
+    ("とぅ", "tu"),
+    ("どぅ", "du"),
+    ("うぃ", "wi"),
+    ("うぇ", "we"),
+    ("うぉ", "wo"),
+    ("ゔぁ", "va"),
+    ("ゔぃ", "vi"),
+    ("ゔぇ", "ve"),
+    ("ゔぉ", "vo"),
+    ("つぁ", "tsa"),
+    ("つぃ", "tsi"),
+    ("つぇ", "tse"),
+    ("つぉ", "tso"),
+];
+
+fn syllable(kana: char, system: Romanization) -> Option<&'static str> {
+    let (_, hepburn, kunrei) = SYLLABLES.iter().find(|(c, _, _)| *c == kana)?;
+    Some(match system {
+        Romanization::Hepburn => hepburn,
+        Romanization::Kunrei => kunrei,
+    })
+}
+
+// a kana of the i column followed by a small ゃ, ゅ or ょ, e.g. きゃ kya, しゃ sha / sya
+fn contracted(kana: char, small: char, system: Romanization) -> Option<String> {
+    let vowel = match small {
+        'ゃ' => 'a',
+        'ゅ' => 'u',
+        'ょ' => 'o',
+        _ => return None,
+    };
+    let stem = syllable(kana, system)?
+        .strip_suffix('i')
+        .filter(|stem| !stem.is_empty() && !["x", "w"].contains(stem))?;
+
+    Some(match stem {
+        // the hepburn system doesn't write the y after these
+        "sh" | "ch" | "j" => format!("{stem}{vowel}"),
+        _ => format!("{stem}y{vowel}"),
+    })
+}
+
+// the romaji of the kana, without macrons: a long vowel is written as it is typed
+// e.g. "とうきょう" -> "toukyou", "ー" -> "-"
+// katakana is read as hiragana, and anything else is kept as it is
+pub fn to_romaji(s: &str, system: Romanization) -> String {
+    let kana: Vec<char> = to_hiragana(s).chars().collect();
+
+    // each kana is spelled first, then っ and ん look at the spelling after them
+    let mut spellings: Vec<(char, String)> = vec![];
+    let mut index = 0;
+    while index < kana.len() {
+        let c = kana[index];
+        let next = kana.get(index + 1).copied();
+
+        let pair = next.and_then(|next| {
+            let loanword = LOANWORDS
+                .iter()
+                .find(|(pair, _)| pair.chars().eq([c, next]))
+                .filter(|_| system == Romanization::Hepburn)
+                .map(|(_, romaji)| romaji.to_string());
+            loanword.or_else(|| contracted(c, next, system))
+        });
+        if let Some(romaji) = pair {
+            spellings.push((c, romaji));
+            index += 2;
+            continue;
+        }
+
+        let romaji = match c {
+            'ー' => "-".to_string(),
+            _ => match syllable(c, system) {
+                Some(romaji) => romaji.to_string(),
+                None => c.to_string(),
+            },
+        };
+        spellings.push((c, romaji));
+        index += 1;
+    }
+
+    let mut romaji = String::new();
+    for (index, (kana, spelling)) in spellings.iter().enumerate() {
+        let next = spellings
+            .get(index + 1)
+            .map(|(_, spelling)| spelling.as_str())
+            .unwrap_or("");
+
+        match kana {
+            // the sokuon doubles the consonant after it, and ch becomes tch in hepburn
+            'っ' => match next.chars().next() {
+                Some('c') if system == Romanization::Hepburn => romaji.push('t'),
+                Some(consonant)
+                    if consonant.is_ascii_lowercase() && !"aiueonx".contains(consonant) =>
+                {
+                    romaji.push(consonant)
+                }
+                _ => romaji.push_str(spelling),
+            },
+            // the apostrophe tells "n'a" (んあ) from "na" (な), and "n'na" (んな) from "nna"
+            // which roman2kana reads as んあ
+            'ん' => {
+                romaji.push('n');
+                if next.starts_with(['a', 'i', 'u', 'e', 'o', 'y', 'n']) {
+                    romaji.push('\'');
+                }
+            }
+            _ => romaji.push_str(spelling),
+        }
+    }
+
+    romaji
+}
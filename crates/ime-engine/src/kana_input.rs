@@ -0,0 +1,93 @@
+// the kana input style: each key of a jis keyboard types the kana printed on it
+// the keys are windows virtual key codes, as the characters of the jis layout can't tell
+// the yen key (ー) from the ro key (ろ), both of which type a backslash
+
+// (virtual key, kana, kana with shift)
+const JIS: [(usize, char, char); 48] = [
+    (0x31, 'ぬ', 'ぬ'), // 1
+    (0x32, 'ふ', 'ふ'), // 2
+    (0x33, 'あ', 'ぁ'), // 3
+    (0x34, 'う', 'ぅ'), // 4
+    (0x35, 'え', 'ぇ'), // 5
+    (0x36, 'お', 'ぉ'), // 6
+    (0x37, 'や', 'ゃ'), // 7
+    (0x38, 'ゆ', 'ゅ'), // 8
+    (0x39, 'よ', 'ょ'), // 9
+    (0x30, 'わ', 'を'), // 0
+    (0xBD, 'ほ', 'ほ'), // -
+    (0xDE, 'へ', 'へ'), // ^
+    (0xDC, 'ー', 'ー'), // ¥
+    (0x51, 'た', 'た'), // Q
+    (0x57, 'て', 'て'), // W
+    (0x45, 'い', 'ぃ'), // E
+    (0x52, 'す', 'す'), // R
+    (0x54, 'か', 'か'), // T
+    (0x59, 'ん', 'ん'), // Y
+    (0x55, 'な', 'な'), // U
+    (0x49, 'に', 'に'), // I
+    (0x4F, 'ら', 'ら'), // O
+    (0x50, 'せ', 'せ'), // P
+    (0xC0, '゛', '゛'), // @
+    (0xDB, '゜', '「'), // [
+    (0x41, 'ち', 'ち'), // A
+    (0x53, 'と', 'と'), // S
+    (0x44, 'し', 'し'), // D
+    (0x46, 'は', 'は'), // F
+    (0x47, 'き', 'き'), // G
+    (0x48, 'く', 'く'), // H
+    (0x4A, 'ま', 'ま'), // J
+    (0x4B, 'の', 'の'), // K
+    (0x4C, 'り', 'り'), // L
+    (0xBB, 'れ', 'れ'), // ;
+    (0xBA, 'け', 'け'), // :
+    (0xDD, 'む', '」'), // ]
+    (0x5A, 'つ', 'っ'), // Z
+    (0x58, 'さ', 'さ'), // X
+    (0x43, 'そ', 'そ'), // C
+    (0x56, 'ひ', 'ひ'), // V
+    (0x42, 'こ', 'こ'), // B
+    (0x4E, 'み', 'み'), // N
+    (0x4D, 'も', 'も'), // M
+    (0xBC, 'ね', '、'), // ,
+    (0xBE, 'る', '。'), // .
+    (0xBF, 'め', '・'), // /
+    (0xE2, 'ろ', 'ろ'), // \
+];
+
+// the kana of the key, None for the keys without one such as the numeric keypad
+pub fn jis_kana(key_code: usize, shift: bool) -> Option<char> {
+    JIS.iter()
+        .find(|(key, _, _)| *key == key_code)
+        .map(|(_, kana, shifted)| if shift { *shifted } else { *kana })
+}
+
+// (the kana, the same kana with the mark) in the same order
+const DAKUTEN: (&str, &str) = (
+    "かきくけこさしすせそたちつてとはひふへほうゝカキクケコサシスセソタチツテトハヒフヘホウヽワ",
+    "がぎぐげござじずぜぞだぢづでどばびぶべぼゔゞガギグゲゴザジズゼゾダヂヅデドバビブベボヴヾヷ",
+);
+const HANDAKUTEN: (&str, &str) = ("はひふへほハヒフヘホ", "ぱぴぷぺぽパピプペポ");
+
+// the kana with the dakuten (゛) or the handakuten (゜) typed after it, None if they don't combine
+pub fn combine(kana: char, mark: char) -> Option<char> {
+    let (plain, marked) = match mark {
+        '゛' => DAKUTEN,
+        '゜' => HANDAKUTEN,
+        _ => return None,
+    };
+
+    plain
+        .chars()
+        .position(|c| c == kana)
+        .and_then(|index| marked.chars().nth(index))
+}
+
+// the kana and the mark of a kana with the dakuten or the handakuten, the reverse of combine
+pub fn split(kana: char) -> Option<(char, char)> {
+    [('゛', DAKUTEN), ('゜', HANDAKUTEN)]
+        .into_iter()
+        .find_map(|(mark, (plain, marked))| {
+            let index = marked.chars().position(|c| c == kana)?;
+            plain.chars().nth(index).map(|plain| (plain, mark))
+        })
+}
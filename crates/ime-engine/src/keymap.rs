@@ -0,0 +1,323 @@
+// keymap of the ime, read from %APPDATA%/Azookey/keymap.toml
+// the file binds keys to commands in each state, on top of a preset:
+//
+//   preset = "atok"             # "ms-ime" (default), "atok" or "kotoeri"
+//   [composing]
+//   "C-S-Left" = "shrink_segment"
+//   "Tab" = "pass"              # unbind the key of the preset
+//
+// [selecting] is looked up before [composing] while a candidate is selected
+// keys which are not bound type their character as they are, and ctrl chords are passed to
+// the application
+
+mod key;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    path::Path,
+    str::FromStr,
+};
+
+use anyhow::{bail, Context as _, Result};
+
+use super::{
+    composition::CompositionState,
+    settings::config_path,
+    user_action::{Function, Navigation, UserAction},
+};
+
+pub use key::{Key, KeyCode, KeyEvent};
+
+const PRESETS: [(&str, &str); 3] = [
+    ("ms-ime", include_str!("keymap/ms_ime.toml")),
+    ("atok", include_str!("keymap/atok.toml")),
+    ("kotoeri", include_str!("keymap/kotoeri.toml")),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Section {
+    None,
+    Composing,
+    Selecting,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    // the key is not bound, and types its character if any
+    Pass,
+    ToggleInputMode,
+    UndoCommit,
+    Reconvert,
+    Convert,
+    ConvertPrev,
+    Commit,
+    Cancel,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    ShrinkSegment,
+    ExpandSegment,
+    Hiragana,
+    Katakana,
+    HalfKatakana,
+    FullAlphanumeric,
+    HalfAlphanumeric,
+}
+
+const COMMANDS: [(&str, Command); 25] = [
+    ("pass", Command::Pass),
+    ("toggle_input_mode", Command::ToggleInputMode),
+    ("undo_commit", Command::UndoCommit),
+    ("reconvert", Command::Reconvert),
+    ("convert", Command::Convert),
+    ("convert_prev", Command::ConvertPrev),
+    ("commit", Command::Commit),
+    ("cancel", Command::Cancel),
+    ("backspace", Command::Backspace),
+    ("delete", Command::Delete),
+    ("left", Command::Left),
+    ("right", Command::Right),
+    ("up", Command::Up),
+    ("down", Command::Down),
+    ("page_up", Command::PageUp),
+    ("page_down", Command::PageDown),
+    ("home", Command::Home),
+    ("end", Command::End),
+    ("shrink_segment", Command::ShrinkSegment),
+    ("expand_segment", Command::ExpandSegment),
+    ("hiragana", Command::Hiragana),
+    ("katakana", Command::Katakana),
+    ("half_katakana", Command::HalfKatakana),
+    ("full_alphanumeric", Command::FullAlphanumeric),
+    ("half_alphanumeric", Command::HalfAlphanumeric),
+];
+
+impl FromStr for Command {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match COMMANDS.iter().find(|(name, _)| *name == s) {
+            Some((_, command)) => Ok(*command),
+            None => bail!("unknown command {s:?}"),
+        }
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = COMMANDS
+            .iter()
+            .find(|(_, c)| c == self)
+            .map(|(name, _)| name);
+        write!(f, "{}", name.unwrap_or(&"?"))
+    }
+}
+
+impl Command {
+    fn action(self) -> Option<UserAction> {
+        let action = match self {
+            Command::Pass => return None,
+            Command::ToggleInputMode => UserAction::ToggleInputMode,
+            Command::UndoCommit => UserAction::UndoCommit,
+            Command::Reconvert => UserAction::Reconvert,
+            Command::Convert => UserAction::Space,
+            Command::ConvertPrev => UserAction::ShiftSpace,
+            Command::Commit => UserAction::Enter,
+            Command::Cancel => UserAction::Escape,
+            Command::Backspace => UserAction::Backspace,
+            Command::Delete => UserAction::Delete,
+            Command::Left => UserAction::Navigation(Navigation::Left),
+            Command::Right => UserAction::Navigation(Navigation::Right),
+            Command::Up => UserAction::Navigation(Navigation::Up),
+            Command::Down => UserAction::Navigation(Navigation::Down),
+            Command::PageUp => UserAction::Navigation(Navigation::PageUp),
+            Command::PageDown => UserAction::Navigation(Navigation::PageDown),
+            Command::Home => UserAction::Navigation(Navigation::Home),
+            Command::End => UserAction::Navigation(Navigation::End),
+            Command::ShrinkSegment => UserAction::ShiftLeft,
+            Command::ExpandSegment => UserAction::ShiftRight,
+            Command::Hiragana => UserAction::Function(Function::Six),
+            Command::Katakana => UserAction::Function(Function::Seven),
+            Command::HalfKatakana => UserAction::Function(Function::Eight),
+            Command::FullAlphanumeric => UserAction::Function(Function::Nine),
+            Command::HalfAlphanumeric => UserAction::Function(Function::Ten),
+        };
+        Some(action)
+    }
+
+    // the other commands work on the composition, so they can't be bound in [none]
+    fn works_without_composition(self) -> bool {
+        matches!(
+            self,
+            Command::Pass | Command::ToggleInputMode | Command::UndoCommit | Command::Reconvert
+        )
+    }
+}
+
+#[derive(Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct KeymapFile {
+    preset: Option<String>,
+    none: BTreeMap<String, String>,
+    composing: BTreeMap<String, String>,
+    selecting: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: HashMap<(Section, Key), Command>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::preset("ms-ime").expect("presets are valid")
+    }
+}
+
+impl Keymap {
+    pub fn preset(name: &str) -> Result<Self> {
+        let (_, text) = PRESETS
+            .iter()
+            .find(|(preset, _)| *preset == name)
+            .with_context(|| format!("unknown preset {name:?}"))?;
+
+        let mut keymap = Keymap {
+            bindings: HashMap::new(),
+        };
+        keymap
+            .apply(toml::from_str(text)?)
+            .with_context(|| format!("invalid preset {name:?}"))?;
+        Ok(keymap)
+    }
+
+    // a keymap file, whose bindings override the ones of the preset
+    pub fn parse(text: &str) -> Result<Self> {
+        let file: KeymapFile = toml::from_str(text)?;
+        let mut keymap = Self::preset(file.preset.as_deref().unwrap_or("ms-ime"))?;
+        keymap.apply(file)?;
+        Ok(keymap)
+    }
+
+    // never fails, the ms-ime preset is used if the file is missing or broken
+    pub fn load() -> Self {
+        let path = match config_path("keymap.toml") {
+            Ok(path) => path,
+            Err(e) => {
+                log::warn!("Failed to locate keymap: {:#}", e);
+                return Self::default();
+            }
+        };
+
+        if !path.exists() {
+            return Self::default();
+        }
+
+        match Self::read(&path) {
+            Ok(keymap) => keymap,
+            Err(e) => {
+                log::warn!("Failed to load keymap from {}: {:#}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    fn read(path: &Path) -> Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    // every problem of the file is reported at once
+    fn apply(&mut self, file: KeymapFile) -> Result<()> {
+        let mut errors = vec![];
+
+        for (section, name, bindings) in [
+            (Section::None, "none", file.none),
+            (Section::Composing, "composing", file.composing),
+            (Section::Selecting, "selecting", file.selecting),
+        ] {
+            // the same key may be written in several ways, e.g. "C-A" and "C-a"
+            let mut seen: HashMap<Key, (&str, Command)> = HashMap::new();
+
+            for (key_name, command_name) in &bindings {
+                let key = match key_name.parse::<Key>() {
+                    Ok(key) => key,
+                    Err(e) => {
+                        errors.push(format!("[{name}] {e}"));
+                        continue;
+                    }
+                };
+                let command = match command_name.parse::<Command>() {
+                    Ok(command) => command,
+                    Err(e) => {
+                        errors.push(format!("[{name}] {key_name:?}: {e}"));
+                        continue;
+                    }
+                };
+
+                if section == Section::None && !command.works_without_composition() {
+                    errors.push(format!(
+                        "[{name}] {key_name:?}: {command} needs a composition"
+                    ));
+                    continue;
+                }
+
+                if let Some((other, bound)) = seen.insert(key, (key_name, command)) {
+                    errors.push(format!(
+                        "[{name}] {key_name:?} = {command} conflicts with {other:?} = {bound}"
+                    ));
+                    continue;
+                }
+
+                self.bindings.insert((section, key), command);
+            }
+        }
+
+        if !errors.is_empty() {
+            bail!("{}", errors.join("\n"));
+        }
+
+        Ok(())
+    }
+
+    // the command bound to the key in the current state
+    pub fn action(&self, state: &CompositionState, event: &KeyEvent) -> UserAction {
+        let sections: &[Section] = match state {
+            CompositionState::None => &[Section::None],
+            CompositionState::Composing | CompositionState::Previewing => &[Section::Composing],
+            CompositionState::Selecting => &[Section::Selecting, Section::Composing],
+        };
+
+        // shift is ignored for the named keys unless bound, e.g. S-Up moves up as Up does
+        let mut keys = vec![event.key];
+        if event.key.shift && !matches!(event.key.code, KeyCode::Char(_)) {
+            keys.push(Key {
+                shift: false,
+                ..event.key
+            });
+        }
+
+        let command = keys.iter().find_map(|key| {
+            sections
+                .iter()
+                .find_map(|section| self.bindings.get(&(*section, *key)))
+        });
+
+        match command.and_then(|command| command.action()) {
+            Some(action) => action,
+            None => match event.text {
+                Some(c) => match c.to_digit(10) {
+                    Some(number) => UserAction::Number(number as i8),
+                    None => UserAction::Input(c),
+                },
+                None => UserAction::Unknown,
+            },
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::Context as _;
+
+// a physical key, independent of the modifiers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyCode {
+    // a key which types a character, written as the character typed without modifiers
+    Char(char),
+    Space,
+    Enter,
+    Backspace,
+    Delete,
+    Tab,
+    Escape,
+    Left,
+    Right,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Function(u8),
+    Zenkaku,
+    Henkan,
+}
+
+const NAMES: [(&str, KeyCode); 16] = [
+    ("Space", KeyCode::Space),
+    ("Enter", KeyCode::Enter),
+    ("BS", KeyCode::Backspace),
+    ("Del", KeyCode::Delete),
+    ("Tab", KeyCode::Tab),
+    ("Esc", KeyCode::Escape),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("Zenkaku", KeyCode::Zenkaku),
+    ("Henkan", KeyCode::Henkan),
+];
+
+// a key with modifiers, written like "C-S-Left"
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    pub code: KeyCode,
+    pub shift: bool,
+    pub ctrl: bool,
+}
+
+impl Key {
+    pub fn new(code: KeyCode) -> Self {
+        Self {
+            code,
+            shift: false,
+            ctrl: false,
+        }
+    }
+}
+
+impl FromStr for Key {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let mut key = Key::new(KeyCode::Space);
+        let mut rest = s;
+
+        // a single "-" is the minus key itself
+        while rest.len() > 2 {
+            if let Some(r) = rest.strip_prefix("C-") {
+                key.ctrl = true;
+                rest = r;
+            } else if let Some(r) = rest.strip_prefix("S-") {
+                key.shift = true;
+                rest = r;
+            } else {
+                break;
+            }
+        }
+
+        let mut chars = rest.chars();
+        key.code = match (chars.next(), chars.next()) {
+            // letters are bound by the key, so "C-A" is the same as "C-a"
+            (Some(c), None) => KeyCode::Char(c.to_ascii_lowercase()),
+            _ => match NAMES.iter().find(|(name, _)| *name == rest) {
+                Some((_, code)) => *code,
+                None => {
+                    let number = rest
+                        .strip_prefix('F')
+                        .and_then(|n| n.parse::<u8>().ok())
+                        .filter(|n| (1..=12).contains(n))
+                        .with_context(|| format!("unknown key {s:?}"))?;
+                    KeyCode::Function(number)
+                }
+            },
+        };
+
+        Ok(key)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "C-")?;
+        }
+        if self.shift {
+            write!(f, "S-")?;
+        }
+        match self.code {
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::Function(n) => write!(f, "F{n}"),
+            code => {
+                let name = NAMES.iter().find(|(_, c)| *c == code).map(|(name, _)| name);
+                write!(f, "{}", name.unwrap_or(&"?"))
+            }
+        }
+    }
+}
+
+// a key stroke reported by the host
+#[derive(Debug, Clone, Copy)]
+pub struct KeyEvent {
+    pub key: Key,
+    // the character typed by the key with the modifiers, None for the named keys and ctrl chords
+    pub text: Option<char>,
+}
@@ -0,0 +1,17 @@
+// composition state machine of the ime, independent of the text services framework
+// the host passes key strokes through the keymap as UserAction, and receives the text through TextSink
+
+pub mod backend;
+pub mod client_action;
+pub mod composition;
+pub mod full_width;
+pub mod input_mode;
+pub mod kana;
+pub mod kana_input;
+pub mod keymap;
+pub mod offline;
+pub mod roman2kana;
+pub mod script;
+pub mod settings;
+pub mod text_sink;
+pub mod user_action;
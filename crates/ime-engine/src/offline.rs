@@ -0,0 +1,150 @@
+use protos::proto::{action::Kind, Script};
+
+use anyhow::{bail, Result};
+
+use super::{
+    backend::{Backend, Snapshot},
+    kana::{is_kana, to_hiragana},
+    roman2kana::{Romaji, Table},
+    script::{self, next_variant},
+};
+
+// used while kkc server is unreachable: the romaji is turned into hiragana locally by the
+// standard table, and there is no candidate
+// the typed keys are not kept, so the F9 and F10 conversions spell the reading in romaji
+#[derive(Debug, Clone, Default)]
+pub struct OfflineBackend {
+    table: Table,
+    // kana before and after the cursor, the pending romaji is between them
+    spell: Vec<char>,
+    cursor: usize,
+    romaji: Romaji,
+    // the script and its variant, cleared by any other action as kkc server does
+    script: Option<(Script, usize)>,
+    // the script of the input mode, there is no conversion which would replace it
+    mode_script: Option<Script>,
+}
+
+impl OfflineBackend {
+    fn apply(&mut self, action: Kind) {
+        if !matches!(action, Kind::ConvertScript(_) | Kind::ModeScript(_)) {
+            self.script = None;
+        }
+
+        match action {
+            Kind::AppendText(text) => {
+                for c in text.chars() {
+                    // full-width letters of the alphanumeric mode are kept as they are
+                    if c.is_ascii() {
+                        self.romaji.push(&self.table, c);
+                    } else {
+                        self.romaji.flush(&self.table);
+                        self.insert(&c.to_string());
+                    }
+                    let confirmed = self.romaji.take_confirmed();
+                    self.insert(&confirmed);
+                }
+            }
+            Kind::RemoveText(_) => {
+                if !self.romaji.pop() && self.cursor > 0 {
+                    self.cursor -= 1;
+                    self.spell.remove(self.cursor);
+                }
+            }
+            Kind::MoveCursor(offset) => {
+                self.flush();
+                let cursor = self.cursor as i32 + offset;
+                self.cursor = cursor.clamp(0, self.spell.len() as i32) as usize;
+            }
+            Kind::ShrinkText(count) => {
+                self.flush();
+                let count = (count.max(0) as usize).min(self.spell.len());
+                self.spell.drain(..count);
+                self.cursor = self.cursor.saturating_sub(count);
+            }
+            Kind::ClearText(_) => {
+                self.spell.clear();
+                self.cursor = 0;
+                self.romaji = Romaji::default();
+            }
+            Kind::ConvertScript(script) => {
+                self.flush();
+                let script = Script::try_from(script).unwrap_or(Script::Hiragana);
+                self.script = Some(next_variant(self.script, script));
+            }
+            Kind::ModeScript(script) => {
+                self.mode_script = Script::try_from(script)
+                    .ok()
+                    .filter(|script| *script != Script::Unspecified);
+            }
+            Kind::MoveSelection(_)
+            | Kind::SelectSuggestion(_)
+            | Kind::MovePage(_)
+            | Kind::ResizeSegment(_)
+            | Kind::MoveSegment(_) => {}
+        }
+    }
+
+    fn insert(&mut self, text: &str) {
+        let chars: Vec<char> = text.chars().collect();
+        self.spell
+            .splice(self.cursor..self.cursor, chars.iter().copied());
+        self.cursor += chars.len();
+    }
+
+    // the pending romaji is only kept at the end of typing
+    fn flush(&mut self) {
+        self.romaji.flush(&self.table);
+        let confirmed = self.romaji.take_confirmed();
+        self.insert(&confirmed);
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        let before: String = self.spell[..self.cursor].iter().collect();
+        let after: String = self.spell[self.cursor..].iter().collect();
+        let pending = self.romaji.pending();
+        let spell = format!("{before}{pending}{after}");
+
+        // the preview is what enter commits, so the pending romaji is resolved in it
+        // e.g. "hon" is shown as ほん, while the spell keeps ほn for the next key
+        let mut romaji = self.romaji.clone();
+        romaji.flush(&self.table);
+        let resolved = format!("{before}{}{after}", romaji.take_confirmed());
+
+        let mode_script = self.mode_script.map(|script| (script, 0));
+        let preview = match self.script.or(mode_script) {
+            Some((script, variant)) => script::convert(script, variant, &resolved, &resolved),
+            None => resolved,
+        };
+
+        Snapshot {
+            preview,
+            spell,
+            cursor: (self.cursor + pending.chars().count()) as i32,
+            ..Default::default()
+        }
+    }
+}
+
+impl Backend for OfflineBackend {
+    fn process_key(&mut self, actions: Vec<Kind>) -> Result<Snapshot> {
+        for action in actions {
+            self.apply(action);
+        }
+
+        Ok(self.snapshot())
+    }
+
+    // there is no dictionary, so only kana is known to be its own reading
+    fn reverse_lookup(&mut self, text: &str) -> Result<Snapshot> {
+        if !is_kana(text) {
+            bail!("the reading of {text:?} is unknown offline");
+        }
+
+        self.spell = to_hiragana(text).chars().collect();
+        self.cursor = self.spell.len();
+        self.romaji = Romaji::default();
+
+        Ok(self.snapshot())
+    }
+}
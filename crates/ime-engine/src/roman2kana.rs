@@ -0,0 +1,321 @@
+// incremental romaji to kana conversion, driven by a table like roman2kana/default.tsv
+// settings.toml selects the table by `romaji = "azik"` ("standard" (default), "azik" or "act"),
+// and %APPDATA%/Azookey/romaji.tsv replaces it
+//
+// the romaji is looked up in a prefix trie as it is typed. it stays pending while a rule may
+// still continue with the next key, and the longest rule is applied when none does, so that
+// "n" waits for "a" (な) but becomes ん before "k"
+
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use anyhow::{bail, Context as _, Result};
+
+use super::settings::{config_path, InputStyle, Settings};
+
+const PRESETS: [(&str, &str); 3] = [
+    ("standard", include_str!("roman2kana/default.tsv")),
+    ("azik", include_str!("roman2kana/azik.tsv")),
+    ("act", include_str!("roman2kana/act.tsv")),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    kana: String,
+    // romaji which stays pending after the kana, e.g. "k" of "kk" -> っ
+    next: String,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Node {
+    rule: Option<Rule>,
+    children: HashMap<char, Node>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Table {
+    root: Node,
+}
+
+impl Default for Table {
+    fn default() -> Self {
+        Self::preset("standard").expect("presets are valid")
+    }
+}
+
+impl Table {
+    // lines of "romaji<TAB>kana[<TAB>next]", blank lines and lines starting with # are skipped
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut table = Table {
+            root: Node::default(),
+        };
+        let mut errors = vec![];
+
+        for (number, line) in text.lines().enumerate() {
+            let number = number + 1;
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let columns: Vec<&str> = line.split('\t').collect();
+            let (romaji, kana, next) = match columns[..] {
+                [romaji, kana] => (romaji, kana, ""),
+                [romaji, kana, next] => (romaji, kana, next),
+                _ => {
+                    errors.push(format!("line {number}: expected 2 or 3 columns"));
+                    continue;
+                }
+            };
+            if romaji.is_empty() {
+                errors.push(format!("line {number}: empty romaji"));
+                continue;
+            }
+            // the next romaji has to be shorter, or the same rule would be applied forever
+            if next.chars().count() >= romaji.chars().count() {
+                errors.push(format!(
+                    "line {number}: {next:?} is not shorter than {romaji:?}"
+                ));
+                continue;
+            }
+
+            let node = romaji.chars().fold(&mut table.root, |node, c| {
+                node.children.entry(c).or_default()
+            });
+            if node.rule.is_some() {
+                errors.push(format!("line {number}: {romaji:?} is defined twice"));
+                continue;
+            }
+            node.rule = Some(Rule {
+                kana: kana.to_string(),
+                next: next.to_string(),
+            });
+        }
+
+        if !errors.is_empty() {
+            bail!("{}", errors.join("\n"));
+        }
+
+        Ok(table)
+    }
+
+    pub fn preset(name: &str) -> Result<Self> {
+        let (_, text) = PRESETS
+            .iter()
+            .find(|(preset, _)| *preset == name)
+            .with_context(|| format!("unknown romaji table {name:?}"))?;
+
+        Self::parse(text).with_context(|| format!("invalid romaji table {name:?}"))
+    }
+
+    fn read(path: &Path) -> Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    fn node(&self, romaji: &str) -> Option<&Node> {
+        romaji
+            .chars()
+            .try_fold(&self.root, |node, c| node.children.get(&c))
+    }
+}
+
+// the kana decided so far, and the romaji which may still become another kana
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Romaji {
+    confirmed: String,
+    pending: String,
+}
+
+impl Romaji {
+    pub fn confirmed(&self) -> &str {
+        &self.confirmed
+    }
+
+    pub fn pending(&self) -> &str {
+        &self.pending
+    }
+
+    // take the confirmed kana, the pending romaji is kept
+    pub fn take_confirmed(&mut self) -> String {
+        std::mem::take(&mut self.confirmed)
+    }
+
+    pub fn push(&mut self, table: &Table, c: char) {
+        let mut romaji = std::mem::take(&mut self.pending);
+        romaji.push(c);
+        self.resolve(table, romaji);
+    }
+
+    // remove the last pending character, false if nothing is pending
+    pub fn pop(&mut self) -> bool {
+        self.pending.pop().is_some()
+    }
+
+    // the pending romaji is converted by its rule if any, or confirmed as it is
+    pub fn flush(&mut self, table: &Table) {
+        let mut romaji = std::mem::take(&mut self.pending);
+
+        while !romaji.is_empty() {
+            match table.node(&romaji).and_then(|node| node.rule.as_ref()) {
+                Some(rule) => {
+                    self.confirmed.push_str(&rule.kana);
+                    romaji = rule.next.clone();
+                }
+                None => {
+                    self.confirmed.push_str(&romaji);
+                    romaji.clear();
+                }
+            }
+        }
+    }
+
+    // the romaji is the pending one followed by a key, so all but the last character are a
+    // prefix of some rule
+    fn resolve(&mut self, table: &Table, mut romaji: String) {
+        loop {
+            match table.node(&romaji) {
+                // a longer rule may still follow
+                Some(node) if !node.children.is_empty() => {
+                    self.pending = romaji;
+                    return;
+                }
+                Some(Node {
+                    rule: Some(rule), ..
+                }) => {
+                    self.confirmed.push_str(&rule.kana);
+                    romaji = rule.next.clone();
+                    if romaji.is_empty() {
+                        return;
+                    }
+                }
+                // no rule continues with the last character, so the romaji before it is resolved
+                // and the last character starts over
+                _ => {
+                    let Some(last) = romaji.pop() else {
+                        return;
+                    };
+                    if romaji.is_empty() {
+                        self.confirmed.push(last);
+                        return;
+                    }
+
+                    match table.node(&romaji).and_then(|node| node.rule.as_ref()) {
+                        Some(rule) => {
+                            self.confirmed.push_str(&rule.kana);
+                            romaji = rule.next.clone();
+                        }
+                        None => {
+                            self.confirmed.push_str(&romaji);
+                            romaji.clear();
+                        }
+                    }
+                    romaji.push(last);
+                }
+            }
+        }
+    }
+}
+
+// convert the whole romaji, a trailing "n" becomes ん
+pub fn to_hiragana(table: &Table, romaji: &str) -> String {
+    let mut converter = Romaji::default();
+    for c in romaji.chars() {
+        converter.push(table, c);
+    }
+    converter.flush(table);
+    converter.take_confirmed()
+}
+
+// the romaji table of the settings, converted on the client so that kkc server only sees kana
+// whichever table it is. the typed romaji is sent while it is pending, and replaced by its kana
+// once it is resolved
+#[derive(Debug, Clone)]
+pub struct Layout {
+    table: Arc<Table>,
+    romaji: Romaji,
+}
+
+// an edit of the spell at the cursor: remove some characters, then append the text
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Replace {
+    pub remove: usize,
+    pub text: String,
+}
+
+impl Layout {
+    pub fn new(table: Table) -> Self {
+        Self {
+            table: Arc::new(table),
+            romaji: Romaji::default(),
+        }
+    }
+
+    pub fn preset(name: &str) -> Result<Self> {
+        Ok(Self::new(Table::preset(name)?))
+    }
+
+    // never fails, the standard table is used if the configured one is broken
+    // None in the kana input style, which types no romaji
+    pub fn load(settings: &Settings) -> Option<Self> {
+        if settings.input_style == InputStyle::Kana {
+            return None;
+        }
+
+        let path = match config_path("romaji.tsv") {
+            Ok(path) => Some(path).filter(|path| path.exists()),
+            Err(e) => {
+                log::warn!("Failed to locate romaji table: {:#}", e);
+                None
+            }
+        };
+
+        let layout = match path {
+            Some(path) => Table::read(&path)
+                .map(Self::new)
+                .with_context(|| format!("in {}", path.display())),
+            None => Self::preset(&settings.romaji),
+        };
+
+        Some(layout.unwrap_or_else(|e| {
+            log::warn!("Failed to load romaji table: {:#}", e);
+            Self::new(Table::default())
+        }))
+    }
+
+    pub fn input(&mut self, text: &str) -> Replace {
+        let remove = self.romaji.pending().chars().count();
+
+        let mut confirmed = String::new();
+        for c in text.chars() {
+            // kana and full-width characters are never a part of the romaji
+            if c.is_ascii() {
+                self.romaji.push(&self.table, c);
+            } else {
+                self.romaji.flush(&self.table);
+                self.romaji.confirmed.push(c);
+            }
+            confirmed.push_str(&self.romaji.take_confirmed());
+        }
+
+        Replace {
+            remove,
+            text: confirmed + self.romaji.pending(),
+        }
+    }
+
+    // the character removed from the spell is the last pending one if any
+    pub fn backspace(&mut self) {
+        self.romaji.pop();
+    }
+
+    // resolve the pending romaji before the spell is converted or committed, e.g. "n" -> ん
+    // None if the spell doesn't change, e.g. a "k" without a rule stays "k"
+    pub fn flush(&mut self) -> Option<Replace> {
+        let pending = self.romaji.pending().to_string();
+        self.romaji.flush(&self.table);
+        let text = self.romaji.take_confirmed();
+
+        (text != pending).then(|| Replace {
+            remove: pending.chars().count(),
+            text,
+        })
+    }
+}
@@ -0,0 +1,66 @@
+// the F6-F10 conversions of the reading, for kkc server and the offline backend alike
+
+use protos::proto::Script;
+
+use super::{
+    full_width::to_full_alphanumeric,
+    kana::{to_half_katakana, to_katakana, to_romaji, Romanization},
+};
+
+// variants cycled by pressing the same function key again
+pub fn variant_count(script: Script) -> usize {
+    match script {
+        Script::FullAlphanumeric | Script::HalfAlphanumeric => 3,
+        _ => 1,
+    }
+}
+
+// pressing the same function key again cycles the variants of the script
+pub fn next_variant(current: Option<(Script, usize)>, script: Script) -> (Script, usize) {
+    match current {
+        Some((current, variant)) if current == script => {
+            (script, (variant + 1) % variant_count(script))
+        }
+        _ => (script, 0),
+    }
+}
+
+// spell is the hiragana reading, raw is the input of the converter
+pub fn convert(script: Script, variant: usize, spell: &str, raw: &str) -> String {
+    match script {
+        Script::Unspecified | Script::Hiragana => spell.to_string(),
+        Script::Katakana => to_katakana(spell),
+        Script::HalfKatakana => to_half_katakana(spell),
+        Script::FullAlphanumeric => {
+            to_full_alphanumeric(&alphanumeric_case(&romaji(spell, raw), variant))
+        }
+        Script::HalfAlphanumeric => alphanumeric_case(&romaji(spell, raw), variant),
+    }
+}
+
+// raw is the typed romaji, unless the keys were resolved into kana before they reached the
+// converter, as the client does for azik and act and the kana input style does
+// the romaji is spelled from the reading then
+fn romaji(spell: &str, raw: &str) -> String {
+    if raw.is_ascii() {
+        raw.to_string()
+    } else {
+        to_romaji(spell, Romanization::Hepburn)
+    }
+}
+
+// lower case, UPPER CASE, then Capitalized
+fn alphanumeric_case(raw: &str, variant: usize) -> String {
+    match variant % 3 {
+        0 => raw.to_lowercase(),
+        1 => raw.to_uppercase(),
+        _ => {
+            let lower = raw.to_lowercase();
+            let mut chars = lower.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => lower,
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result};
+
+// user settings, read from %APPDATA%/Azookey/settings.toml
+// missing keys fall back to the defaults
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // number of candidates in a page of the candidate window
+    pub page_size: u32,
+    pub conversion_style: ConversionStyle,
+    pub input_style: InputStyle,
+    // romaji table, "standard", "azik" or "act"
+    pub romaji: String,
+    // executables launched when the servers are not running, next to the ime dll if unset
+    pub server_path: Option<PathBuf>,
+    pub ui_path: Option<PathBuf>,
+}
+
+// conversion_style = "live" or "explicit"
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConversionStyle {
+    // the first candidate is previewed while typing
+    #[default]
+    Live,
+    // the reading is previewed until space is pressed, like ms-ime
+    Explicit,
+}
+
+// input_style = "romaji" or "kana"
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InputStyle {
+    #[default]
+    Romaji,
+    // the keys type the kana printed on a jis keyboard
+    Kana,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            // 9 candidates fit the number keys
+            page_size: 9,
+            conversion_style: ConversionStyle::default(),
+            input_style: InputStyle::default(),
+            romaji: "standard".to_string(),
+            server_path: None,
+            ui_path: None,
+        }
+    }
+}
+
+impl Settings {
+    // never fails, the defaults are used if the file is missing or broken
+    pub fn load() -> Self {
+        let path = match config_path("settings.toml") {
+            Ok(path) => path,
+            Err(e) => {
+                log::warn!("Failed to locate settings: {:#}", e);
+                return Self::default();
+            }
+        };
+
+        if !path.exists() {
+            return Self::default();
+        }
+
+        match Self::read(&path) {
+            Ok(settings) => settings,
+            Err(e) => {
+                log::warn!("Failed to load settings from {}: {:#}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    fn read(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        let settings: Settings = toml::from_str(&text)?;
+
+        if settings.page_size == 0 {
+            anyhow::bail!("page_size must be greater than 0");
+        }
+
+        Ok(settings)
+    }
+}
+
+// files of the user are kept in %APPDATA%/Azookey
+pub(crate) fn config_path(name: &str) -> Result<PathBuf> {
+    let app_data = std::env::var("APPDATA").context("APPDATA is not set")?;
+    Ok(PathBuf::from(app_data).join("Azookey").join(name))
+}
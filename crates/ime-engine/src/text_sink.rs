@@ -0,0 +1,28 @@
+use anyhow::Result;
+
+use super::input_mode::InputMode;
+
+// the text field of the host application, e.g. a composition of the text services framework
+pub trait TextSink {
+    fn start_composition(&mut self) -> Result<()>;
+
+    // start a composition over the text just before the caret
+    // false if the text there is different, e.g. the caret has been moved
+    fn start_composition_over(&mut self, text: &str) -> Result<bool>;
+
+    // the text selected in the document, empty if nothing is selected
+    fn selected_text(&mut self) -> Result<String>;
+
+    // start a composition over the selected text
+    fn start_composition_over_selection(&mut self) -> Result<()>;
+    fn end_composition(&mut self) -> Result<()>;
+
+    // text is shown as the conversion, and subtext is appended after it
+    fn set_text(&mut self, text: &str, subtext: &str) -> Result<()>;
+
+    // position is counted in utf-16 from the start of the composition
+    fn set_cursor(&mut self, position: i32) -> Result<()>;
+
+    // the composition is already ended when this is called
+    fn set_input_mode(&mut self, mode: InputMode) -> Result<()>;
+}
@@ -0,0 +1,41 @@
+// commands of the engine, decoded from the key strokes by the keymap
+#[derive(Debug)]
+pub enum UserAction {
+    Input(char),
+    Backspace,
+    Delete,
+    Enter,
+    Space,
+    ShiftSpace,
+    ShiftLeft,
+    ShiftRight,
+    Escape,
+    Unknown,
+    Navigation(Navigation),
+    Function(Function),
+    Number(i8),
+    ToggleInputMode,
+    UndoCommit,
+    Reconvert,
+}
+
+#[derive(Debug)]
+pub enum Navigation {
+    Up,
+    Down,
+    Left,
+    Right,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+}
+
+#[derive(Debug)]
+pub enum Function {
+    Six,
+    Seven,
+    Eight,
+    Nine,
+    Ten,
+}
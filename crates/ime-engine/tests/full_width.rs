@@ -0,0 +1,97 @@
+// the width conversion of ascii and katakana, every character has to survive a round trip
+
+use ime_engine::full_width::{to_full, to_fullwidth, to_half, Classes};
+
+// every combination of the classes
+fn all_classes() -> Vec<Classes> {
+    (0..8)
+        .map(|bits| Classes {
+            digits: bits & 1 != 0,
+            letters: bits & 2 != 0,
+            symbols: bits & 4 != 0,
+        })
+        .collect()
+}
+
+#[test]
+fn ascii() {
+    assert_eq!(to_full("Az09 !~", Classes::ALL), "Ａｚ０９\u{3000}！～");
+    assert_eq!(to_half("Ａｚ０９\u{3000}！～", Classes::ALL), "Az09 !~");
+
+    // only the classes which are asked for are converted, in both directions
+    let digits = Classes {
+        digits: true,
+        letters: false,
+        symbols: false,
+    };
+    assert_eq!(to_full("a1!", digits), "a１!");
+    assert_eq!(to_half("ａ１！", digits), "ａ1！");
+    assert_eq!(to_full("a1! ", Classes::SYMBOLS), "a1！\u{3000}");
+}
+
+#[test]
+fn ascii_round_trip() {
+    for classes in all_classes() {
+        for c in ' '..='~' {
+            let text = c.to_string();
+            let full = to_full(&text, classes);
+            assert_eq!(full != text, classes.contains(c), "{c:?} {classes:?}");
+            assert_eq!(to_half(&full, classes), text, "{c:?} {classes:?}");
+        }
+
+        for c in ('\u{FF01}'..='\u{FF5E}').chain(['\u{3000}']) {
+            let text = c.to_string();
+            assert_eq!(to_full(&to_half(&text, classes), classes), text, "{c:?}");
+        }
+    }
+}
+
+#[test]
+fn katakana() {
+    assert_eq!(to_full("ｶﾞｯｺｳ", Classes::ALL), "ガッコウ");
+    assert_eq!(to_full("ﾊﾟﾝﾀﾞ", Classes::ALL), "パンダ");
+    assert_eq!(to_full("ｳﾞｧｲｵﾘﾝ", Classes::ALL), "ヴァイオリン");
+    assert_eq!(to_full("ｰ｡｢｣､･", Classes::ALL), "ー。「」、・");
+
+    // a mark which doesn't combine is left as a full-width mark
+    assert_eq!(to_full("ｱﾞﾟ", Classes::ALL), "ア゛゜");
+    assert_eq!(to_full("ﾞｶ", Classes::ALL), "゛カ");
+
+    assert_eq!(to_half("ガッコウ", Classes::ALL), "ｶﾞｯｺｳ");
+    assert_eq!(to_half("ヴァイオリン", Classes::ALL), "ｳﾞｧｲｵﾘﾝ");
+    assert_eq!(to_half("ヷ", Classes::ALL), "ﾜﾞ");
+
+    // katakana is converted regardless of the classes, and hiragana never is
+    assert_eq!(to_full("ｶa", Classes::NONE), "カa");
+    assert_eq!(to_half("カがヮ", Classes::NONE), "ｶがヮ");
+}
+
+#[test]
+fn katakana_round_trip() {
+    let half: Vec<char> = ('\u{FF61}'..='\u{FF9F}').collect();
+
+    // every half-width katakana, alone and followed by each mark
+    for &c in &half {
+        for text in [c.to_string(), format!("{c}ﾞ"), format!("{c}ﾟ")] {
+            let full = to_full(&text, Classes::ALL);
+            assert!(!full.chars().any(|c| half.contains(&c)), "{text}");
+            assert_eq!(to_half(&full, Classes::ALL), text, "{text}");
+        }
+    }
+
+    // every full-width katakana which has a half-width form
+    for c in '\u{3000}'..='\u{30FF}' {
+        let text = c.to_string();
+        let narrow = to_half(&text, Classes::ALL);
+        if narrow != text {
+            assert_eq!(to_full(&narrow, Classes::ALL), text, "{c}");
+        }
+    }
+}
+
+#[test]
+fn kana_modes() {
+    // the punctuation typed in the kana modes is the japanese one, and letters are kept
+    assert_eq!(to_fullwidth("ka,n-ji.!?"), "ka、nーji。！？");
+    assert_eq!(to_fullwidth("2"), "2");
+}
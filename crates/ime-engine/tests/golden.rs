@@ -0,0 +1,55 @@
+// runs every tests/golden/*.keys, and compares the log with the .golden file next to it
+// the settings are read from the .toml file of the same name if it exists,
+// and the keymap from the .keymap.toml file
+// set UPDATE_GOLDEN=1 to write the current logs as the golden files
+
+mod harness;
+
+use std::{fs, path::Path};
+
+use ime_engine::{keymap::Keymap, settings::Settings};
+
+#[test]
+fn golden() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+
+    let mut scripts: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "keys"))
+        .collect();
+    scripts.sort();
+    assert!(!scripts.is_empty(), "no key scripts in {}", dir.display());
+
+    let mut failures = vec![];
+    for script in scripts {
+        let settings = match fs::read_to_string(script.with_extension("toml")) {
+            Ok(text) => toml::from_str(&text).unwrap(),
+            Err(_) => Settings::default(),
+        };
+        let keymap = match fs::read_to_string(script.with_extension("keymap.toml")) {
+            Ok(text) => Keymap::parse(&text).unwrap(),
+            Err(_) => Keymap::default(),
+        };
+        let log = harness::run(&fs::read_to_string(&script).unwrap(), &settings, &keymap)
+            .unwrap_or_else(|e| panic!("{}: {:#}", script.display(), e));
+        let golden = script.with_extension("golden");
+
+        if update {
+            fs::write(&golden, &log).unwrap();
+            continue;
+        }
+
+        match fs::read_to_string(&golden) {
+            Ok(expected) if expected == log => (),
+            Ok(expected) => failures.push(format!(
+                "{} differs\n--- expected\n{expected}--- actual\n{log}",
+                golden.display()
+            )),
+            Err(_) => failures.push(format!("{} is missing", golden.display())),
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -0,0 +1,313 @@
+// feeds a key script through the composition engine, and records what the host would see
+// the backend is the session of the server with the stub converter, so no swift is needed
+
+use std::{cell::RefCell, fmt::Write as _, rc::Rc};
+
+use anyhow::{bail, Result};
+use azookey_server::{
+    converter::stub::StubConverter,
+    session::Session,
+    window::{self, WindowUpdate},
+};
+use ime_engine::{
+    backend::{Backend, Snapshot},
+    client_action::ClientAction,
+    composition::{Composition, CompositionState},
+    input_mode::InputMode,
+    keymap::{Key, KeyCode, KeyEvent, Keymap},
+    roman2kana::Layout,
+    settings::{ConversionStyle, InputStyle, Settings},
+    text_sink::TextSink,
+};
+use protos::proto::{self, action::Kind};
+
+// a key of the script
+pub enum Stroke {
+    Key(KeyEvent),
+    // selecting the mode from the menu of the language bar
+    Mode(InputMode),
+    // selecting the last characters of the document with the mouse
+    Select(usize),
+}
+
+// plain characters are typed as they are, and named keys are written in angle brackets
+// e.g. "kyouhaiitenki<Space><Space><Enter>", "<S-Left>", "<C-z>", "<Mode-Katakana>", "<Select-2>"
+// lines starting with # are comments, and line breaks are ignored
+pub fn parse(script: &str) -> Result<Vec<(String, Stroke)>> {
+    let mut strokes = vec![];
+
+    for line in script.lines() {
+        if line.starts_with('#') {
+            continue;
+        }
+
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            if c != '<' {
+                let event = match c {
+                    ' ' => KeyEvent {
+                        key: Key::new(KeyCode::Space),
+                        text: None,
+                    },
+                    _ => KeyEvent {
+                        key: Key {
+                            code: KeyCode::Char(c.to_ascii_lowercase()),
+                            shift: c.is_ascii_uppercase(),
+                            ctrl: false,
+                        },
+                        text: Some(c),
+                    },
+                };
+                strokes.push((c.to_string(), Stroke::Key(event)));
+                continue;
+            }
+
+            let name: String = chars.by_ref().take_while(|&c| c != '>').collect();
+            strokes.push((format!("<{name}>"), named(&name)?));
+        }
+    }
+
+    Ok(strokes)
+}
+
+fn named(name: &str) -> Result<Stroke> {
+    if let Some(mode) = name.strip_prefix("Mode-") {
+        return match InputMode::ALL.iter().find(|m| format!("{m:?}") == mode) {
+            Some(mode) => Ok(Stroke::Mode(mode.clone())),
+            None => bail!("unknown mode <{name}>"),
+        };
+    }
+
+    if let Some(count) = name.strip_prefix("Select-") {
+        return Ok(Stroke::Select(count.parse()?));
+    }
+
+    Ok(Stroke::Key(KeyEvent {
+        key: name.parse()?,
+        text: None,
+    }))
+}
+
+type Log = Rc<RefCell<String>>;
+
+// the composition of the host, the text given to end_composition is committed to the document
+struct RecordingSink {
+    log: Log,
+    mode: Rc<RefCell<InputMode>>,
+    document: String,
+    // number of the characters selected at the end of the document
+    selection: usize,
+    text: String,
+    composing: bool,
+}
+
+impl RecordingSink {
+    fn selection_start(&self) -> usize {
+        let count = self.document.chars().count();
+        self.document
+            .char_indices()
+            .nth(count.saturating_sub(self.selection))
+            .map_or(self.document.len(), |(index, _)| index)
+    }
+}
+
+impl TextSink for RecordingSink {
+    fn start_composition(&mut self) -> Result<()> {
+        self.selection = 0;
+        self.composing = true;
+        self.text.clear();
+        writeln!(self.log.borrow_mut(), "  start")?;
+        Ok(())
+    }
+
+    fn start_composition_over(&mut self, text: &str) -> Result<bool> {
+        let Some(rest) = self.document.strip_suffix(text) else {
+            return Ok(false);
+        };
+        self.document.truncate(rest.len());
+        self.composing = true;
+        self.text = text.to_string();
+        writeln!(self.log.borrow_mut(), "  start over {text:?}")?;
+        Ok(true)
+    }
+
+    fn selected_text(&mut self) -> Result<String> {
+        Ok(self.document[self.selection_start()..].to_string())
+    }
+
+    fn start_composition_over_selection(&mut self) -> Result<()> {
+        let start = self.selection_start();
+        self.text = self.document.split_off(start);
+        self.selection = 0;
+        self.composing = true;
+        writeln!(self.log.borrow_mut(), "  start over {:?}", self.text)?;
+        Ok(())
+    }
+
+    fn end_composition(&mut self) -> Result<()> {
+        if self.composing {
+            writeln!(self.log.borrow_mut(), "  commit {:?}", self.text)?;
+            self.document.push_str(&self.text);
+        }
+        self.composing = false;
+        self.text.clear();
+        Ok(())
+    }
+
+    fn set_text(&mut self, text: &str, subtext: &str) -> Result<()> {
+        // the text is dropped by tsf when there is no composition
+        if self.composing {
+            self.text = format!("{text}{subtext}");
+            writeln!(self.log.borrow_mut(), "  text {text:?} {subtext:?}")?;
+        }
+        Ok(())
+    }
+
+    fn set_cursor(&mut self, position: i32) -> Result<()> {
+        writeln!(self.log.borrow_mut(), "  cursor {position}")?;
+        Ok(())
+    }
+
+    fn set_input_mode(&mut self, mode: InputMode) -> Result<()> {
+        writeln!(self.log.borrow_mut(), "  mode {mode:?}")?;
+        *self.mode.borrow_mut() = mode;
+        Ok(())
+    }
+}
+
+// the session of the server, which also reports the updates of the candidate window
+struct StubBackend {
+    log: Log,
+    session: Session,
+}
+
+impl Backend for StubBackend {
+    fn process_key(&mut self, actions: Vec<Kind>) -> Result<Snapshot> {
+        for action in actions {
+            self.session.apply(action);
+        }
+        let snapshot = self.session.snapshot();
+
+        let mut log = self.log.borrow_mut();
+        match self.session.window_update(&snapshot) {
+            WindowUpdate::Show {
+                candidates,
+                selection,
+                page_size,
+            } => {
+                let page = window::page(&candidates, selection, page_size);
+                let rows: Vec<String> = page
+                    .rows
+                    .iter()
+                    .map(|row| format!("{}:{}", row.shortcut, row.text))
+                    .collect();
+                writeln!(
+                    log,
+                    "  window page {}/{} selection {} [{}]",
+                    page.page,
+                    page.page_count,
+                    selection % page_size,
+                    rows.join(" ")
+                )?;
+            }
+            WindowUpdate::Hide => writeln!(log, "  window hide")?,
+        }
+
+        Ok(snapshot.into())
+    }
+
+    fn reverse_lookup(&mut self, text: &str) -> Result<Snapshot> {
+        // the server answers with an error, which the composition only logs
+        let Some(reading) = self.session.reverse_lookup(text) else {
+            writeln!(self.log.borrow_mut(), "  unknown reading")?;
+            bail!("the reading of {text:?} is unknown");
+        };
+        writeln!(self.log.borrow_mut(), "  reading {reading:?}")?;
+        self.process_key(vec![])
+    }
+}
+
+// run the script from a fresh composition in the latin mode, and return the log
+pub fn run(script: &str, settings: &Settings, keymap: &Keymap) -> Result<String> {
+    let log = Log::default();
+    let mode = Rc::new(RefCell::new(InputMode::default()));
+    let conversion_style = match settings.conversion_style {
+        ConversionStyle::Live => proto::ConversionStyle::Live,
+        ConversionStyle::Explicit => proto::ConversionStyle::Explicit,
+    };
+    let input_style = match settings.input_style {
+        InputStyle::Romaji => proto::InputStyle::Romaji,
+        InputStyle::Kana => proto::InputStyle::Kana,
+    };
+
+    let mut sink = RecordingSink {
+        log: log.clone(),
+        mode: mode.clone(),
+        document: String::new(),
+        selection: 0,
+        text: String::new(),
+        composing: false,
+    };
+    let mut backend = StubBackend {
+        log: log.clone(),
+        session: Session::new(
+            Box::new(StubConverter::default()),
+            settings.page_size,
+            conversion_style,
+            input_style,
+        ),
+    };
+    let mut composition = Composition {
+        layout: match settings.input_style {
+            InputStyle::Romaji => Some(Layout::preset(&settings.romaji)?),
+            InputStyle::Kana => None,
+        },
+        ..Default::default()
+    };
+
+    for (name, stroke) in parse(script)? {
+        writeln!(log.borrow_mut(), "{name}")?;
+
+        let event = match stroke {
+            Stroke::Key(event) => event,
+            // the caret is moved by the mouse
+            Stroke::Select(count) => {
+                sink.selection = count;
+                composition.forget_commit();
+                continue;
+            }
+            // the composition is committed in the current mode, as the tsf layer does
+            Stroke::Mode(selected) => {
+                let current = mode.borrow().clone();
+                let actions = [ClientAction::SetIMEMode(selected)];
+                composition.handle_action(
+                    &actions,
+                    CompositionState::None,
+                    &current,
+                    &mut sink,
+                    &mut backend,
+                )?;
+                writeln!(log.borrow_mut(), "  state {:?}", composition.state)?;
+                continue;
+            }
+        };
+
+        let action = keymap.action(&composition.state, &event);
+        let current = mode.borrow().clone();
+        let Some((transition, actions)) = composition.transition(&action, &current, settings)
+        else {
+            composition.forget_commit();
+            writeln!(log.borrow_mut(), "  pass")?;
+            continue;
+        };
+
+        if !composition.handle_action(&actions, transition, &current, &mut sink, &mut backend)? {
+            writeln!(log.borrow_mut(), "  pass")?;
+            continue;
+        }
+        writeln!(log.borrow_mut(), "  state {:?}", composition.state)?;
+    }
+
+    let log = log.borrow().clone();
+    Ok(log)
+}
@@ -0,0 +1,120 @@
+// the script conversion of kana, and the romaji spelled back from it
+
+use ime_engine::{
+    kana::{is_kana, to_half_katakana, to_hiragana, to_katakana, to_romaji, Romanization},
+    roman2kana::{self, Table},
+};
+
+#[test]
+fn scripts() {
+    assert_eq!(to_katakana("きょうはいいてんき"), "キョウハイイテンキ");
+    assert_eq!(to_katakana("ゔぁゕゖゝゞ、abc"), "ヴァヵヶヽヾ、abc");
+    assert_eq!(to_hiragana("キョウハイイテンキ"), "きょうはいいてんき");
+    assert_eq!(to_hiragana("ヴァヵヶヽヾ、ー"), "ゔぁゕゖゝゞ、ー");
+    assert_eq!(to_hiragana("ｶﾞｯｺｳﾊﾟﾝ"), "がっこうぱん");
+    assert_eq!(to_half_katakana("がっこう、ぱーてぃー"), "ｶﾞｯｺｳ､ﾊﾟｰﾃｨｰ");
+
+    // the katakana without a hiragana is kept
+    assert_eq!(to_hiragana("ヷヺ"), "ヷヺ");
+
+    for c in '\u{3041}'..='\u{3096}' {
+        let text = c.to_string();
+        assert_eq!(to_hiragana(&to_katakana(&text)), text);
+    }
+}
+
+#[test]
+fn kana_only() {
+    assert!(is_kana("きょうはパーティー"));
+    assert!(is_kana("ｶﾞｯｺｳ"));
+    assert!(!is_kana("漢字"));
+    assert!(!is_kana("kana"));
+    assert!(!is_kana("かな。"));
+    assert!(!is_kana(""));
+}
+
+#[test]
+fn hepburn() {
+    for (kana, romaji) in [
+        ("しんぶん", "shinbun"),
+        ("ちず", "chizu"),
+        ("つくえ", "tsukue"),
+        ("ふじさん", "fujisan"),
+        ("じゃんけん", "janken"),
+        ("ちゃいろ", "chairo"),
+        ("きょうと", "kyouto"),
+        ("りゅう", "ryuu"),
+        ("ひゃく", "hyaku"),
+        ("ぢ", "ji"),
+        ("を", "wo"),
+        // sokuon
+        ("がっこう", "gakkou"),
+        ("まっちゃ", "matcha"),
+        ("ざっし", "zasshi"),
+        ("あっ", "axtsu"),
+        // ん before a vowel, y or n
+        ("きんえん", "kin'en"),
+        ("ほんや", "hon'ya"),
+        ("こんにちは", "kon'nichiha"),
+        // long vowels and loanwords
+        ("ぱーてぃー", "pa-ti-"),
+        ("ふぁいる", "fairu"),
+        ("ゔぃおら", "viora"),
+        ("ちぇっく", "chekku"),
+        // small kana alone
+        ("ぁゃ", "xaxya"),
+        // katakana and half-width katakana
+        ("コーヒー", "ko-hi-"),
+        ("ｼｬｯﾀｰ", "shatta-"),
+        // anything else is kept
+        ("漢字とabc", "漢字toabc"),
+    ] {
+        assert_eq!(to_romaji(kana, Romanization::Hepburn), romaji, "{kana}");
+    }
+}
+
+#[test]
+fn kunrei() {
+    for (kana, romaji) in [
+        ("しんぶん", "sinbun"),
+        ("ちず", "tizu"),
+        ("つくえ", "tukue"),
+        ("ふじさん", "huzisan"),
+        ("じゃんけん", "zyanken"),
+        ("ちゃいろ", "tyairo"),
+        ("しょうゆ", "syouyu"),
+        ("まっちゃ", "mattya"),
+        ("あっ", "axtu"),
+        ("きんえん", "kin'en"),
+        ("ほんを", "honwo"),
+        // no spelling for the loanwords, so they are spelled kana by kana
+        ("ふぁいる", "huxairu"),
+    ] {
+        assert_eq!(to_romaji(kana, Romanization::Kunrei), romaji, "{kana}");
+    }
+}
+
+// the romaji is typed back into the same kana, unless the spelling is shared (ぢ and じ)
+#[test]
+fn round_trip() {
+    let table = Table::default();
+    for kana in [
+        "きょうはいいてんきです",
+        "しゃしんをとった",
+        "ざっしとまっちゃ",
+        "きんえんのほんや",
+        "ふぁいるをひらく",
+        "ちぇっくした",
+        "ひゃくえん",
+        "りょこう",
+    ] {
+        for system in [Romanization::Hepburn, Romanization::Kunrei] {
+            let romaji = to_romaji(kana, system);
+            assert_eq!(
+                roman2kana::to_hiragana(&table, &romaji),
+                kana,
+                "{romaji} {system:?}"
+            );
+        }
+    }
+}
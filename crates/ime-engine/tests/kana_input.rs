@@ -0,0 +1,45 @@
+// the keys of a jis keyboard in the kana input style
+
+use ime_engine::kana_input::{combine, jis_kana};
+
+#[test]
+fn keys() {
+    for (key_code, shift, kana) in [
+        (0x34, false, 'う'), // 4
+        (0x34, true, 'ぅ'),
+        (0x30, false, 'わ'), // 0
+        (0x30, true, 'を'),
+        (0x5A, true, 'っ'),  // Z
+        (0xDC, false, 'ー'), // ¥
+        (0xE2, false, 'ろ'), // \
+        (0xC0, false, '゛'), // @
+        (0xDB, false, '゜'), // [
+        (0xDB, true, '「'),
+        (0xBE, true, '。'), // .
+        (0x51, true, 'た'), // Q, shift types the same kana
+    ] {
+        assert_eq!(jis_kana(key_code, shift), Some(kana), "{key_code:#X}");
+    }
+
+    // the numeric keypad and the named keys have no kana
+    assert_eq!(jis_kana(0x64, false), None);
+    assert_eq!(jis_kana(0x20, false), None);
+}
+
+#[test]
+fn marks() {
+    for (kana, mark, combined) in [
+        ('か', '゛', 'が'),
+        ('つ', '゛', 'づ'),
+        ('う', '゛', 'ゔ'),
+        ('は', '゜', 'ぱ'),
+        ('ホ', '゜', 'ポ'),
+        ('ウ', '゛', 'ヴ'),
+    ] {
+        assert_eq!(combine(kana, mark), Some(combined), "{kana}{mark}");
+    }
+
+    assert_eq!(combine('か', '゜'), None);
+    assert_eq!(combine('あ', '゛'), None);
+    assert_eq!(combine('か', 'ー'), None);
+}
@@ -0,0 +1,142 @@
+// the presets, and the validation of the keymap files
+
+use ime_engine::{
+    composition::CompositionState,
+    keymap::{Key, KeyEvent, Keymap},
+    user_action::UserAction,
+};
+
+fn action(keymap: &Keymap, state: CompositionState, key: &str) -> UserAction {
+    let event = KeyEvent {
+        key: key.parse().unwrap(),
+        text: None,
+    };
+    keymap.action(&state, &event)
+}
+
+#[test]
+fn presets() {
+    for name in ["ms-ime", "atok", "kotoeri"] {
+        let keymap = Keymap::preset(name).unwrap_or_else(|e| panic!("{name}: {e:#}"));
+        assert!(matches!(
+            action(&keymap, CompositionState::Composing, "Space"),
+            UserAction::Space
+        ));
+    }
+    assert!(Keymap::preset("skk").is_err());
+}
+
+#[test]
+fn keys() {
+    for (text, canonical) in [
+        ("S-Space", "S-Space"),
+        ("C-S-Left", "C-S-Left"),
+        ("S-C-Left", "C-S-Left"),
+        ("C-A", "C-a"),
+        ("C--", "C--"),
+        ("F10", "F10"),
+    ] {
+        assert_eq!(text.parse::<Key>().unwrap().to_string(), canonical);
+    }
+
+    for text in ["", "S-", "Spaec", "F13", "C-ab"] {
+        assert!(text.parse::<Key>().is_err(), "{text:?} was parsed");
+    }
+}
+
+#[test]
+fn lookup() {
+    let keymap = Keymap::default();
+
+    // typed characters are not bound
+    let event = KeyEvent {
+        key: "a".parse().unwrap(),
+        text: Some('a'),
+    };
+    assert!(matches!(
+        keymap.action(&CompositionState::None, &event),
+        UserAction::Input('a')
+    ));
+    let event = KeyEvent {
+        key: "1".parse().unwrap(),
+        text: Some('1'),
+    };
+    assert!(matches!(
+        keymap.action(&CompositionState::Composing, &event),
+        UserAction::Number(1)
+    ));
+
+    // conversion keys do nothing without a composition
+    assert!(matches!(
+        action(&keymap, CompositionState::None, "Space"),
+        UserAction::Unknown
+    ));
+    // shift falls back to the key without shift
+    assert!(matches!(
+        action(&keymap, CompositionState::Selecting, "S-Down"),
+        UserAction::Navigation(_)
+    ));
+}
+
+#[test]
+fn overrides() {
+    let keymap = Keymap::parse(
+        r#"
+        preset = "atok"
+        [composing]
+        "Space" = "pass"
+        "C-n" = "convert"
+        [selecting]
+        "Enter" = "cancel"
+        "#,
+    )
+    .unwrap();
+
+    assert!(matches!(
+        action(&keymap, CompositionState::Composing, "Space"),
+        UserAction::Unknown
+    ));
+    assert!(matches!(
+        action(&keymap, CompositionState::Composing, "C-n"),
+        UserAction::Space
+    ));
+    assert!(matches!(
+        action(&keymap, CompositionState::Composing, "Enter"),
+        UserAction::Enter
+    ));
+    assert!(matches!(
+        action(&keymap, CompositionState::Selecting, "Enter"),
+        UserAction::Escape
+    ));
+}
+
+#[test]
+fn validation() {
+    for (text, error) in [
+        (r#"preset = "skk""#, r#"unknown preset "skk""#),
+        (
+            "[composing]\n\"Space\" = \"henkan\"",
+            r#"[composing] "Space": unknown command "henkan""#,
+        ),
+        (
+            "[composing]\n\"Spaec\" = \"convert\"",
+            r#"[composing] unknown key "Spaec""#,
+        ),
+        (
+            "[none]\n\"Space\" = \"convert\"",
+            r#"[none] "Space": convert needs a composition"#,
+        ),
+        (
+            "[composing]\n\"C-a\" = \"convert\"\n\"C-A\" = \"commit\"",
+            r#"[composing] "C-a" = convert conflicts with "C-A" = commit"#,
+        ),
+        ("[composeing]", "unknown field"),
+    ] {
+        let e = format!("{:#}", Keymap::parse(text).unwrap_err());
+        assert!(e.contains(error), "{e:?} doesn't contain {error:?}");
+    }
+
+    // every problem is reported at once
+    let e = Keymap::parse("[composing]\n\"A\" = \"a\"\n\"B\" = \"b\"").unwrap_err();
+    assert_eq!(e.to_string().lines().count(), 2);
+}
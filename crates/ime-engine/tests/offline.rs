@@ -0,0 +1,120 @@
+// the hiragana input while kkc server is unreachable
+
+use ime_engine::{backend::Backend, offline::OfflineBackend};
+use protos::proto::{
+    action::{ClearText, Kind, RemoveText},
+    Script,
+};
+
+fn typed(backend: &mut OfflineBackend, text: &str) -> (String, i32) {
+    let actions = text
+        .chars()
+        .map(|c| Kind::AppendText(c.to_string()))
+        .collect();
+    let snapshot = backend.process_key(actions).unwrap();
+    assert!(snapshot.candidates.is_empty());
+    (snapshot.spell, snapshot.cursor)
+}
+
+#[test]
+fn romaji() {
+    let mut backend = OfflineBackend::default();
+    assert_eq!(typed(&mut backend, "kyouha"), ("きょうは".to_string(), 4));
+    assert_eq!(
+        typed(&mut backend, "kitte"),
+        ("きょうはきって".to_string(), 7)
+    );
+
+    backend
+        .process_key(vec![Kind::ClearText(ClearText {})])
+        .unwrap();
+    assert_eq!(typed(&mut backend, "kanji"), ("かんじ".to_string(), 3));
+
+    // full-width letters of the alphanumeric mode are not converted
+    backend
+        .process_key(vec![Kind::ClearText(ClearText {})])
+        .unwrap();
+    assert_eq!(typed(&mut backend, "ｋａ"), ("ｋａ".to_string(), 2));
+}
+
+#[test]
+fn pending() {
+    let mut backend = OfflineBackend::default();
+    let actions = "hon".chars().map(|c| Kind::AppendText(c.to_string()));
+
+    // the preview is committed by enter, so the pending romaji is resolved there
+    let snapshot = backend.process_key(actions.collect()).unwrap();
+    assert_eq!((snapshot.spell.as_str(), snapshot.cursor), ("ほn", 2));
+    assert_eq!(snapshot.preview, "ほん");
+
+    let snapshot = backend
+        .process_key(vec![Kind::AppendText("a".to_string())])
+        .unwrap();
+    assert_eq!(
+        (snapshot.spell.as_str(), snapshot.preview.as_str()),
+        ("ほな", "ほな")
+    );
+
+    let snapshot = backend
+        .process_key(vec![Kind::AppendText("k".to_string())])
+        .unwrap();
+    assert_eq!(snapshot.preview, "ほなk");
+}
+
+#[test]
+fn edit() {
+    let mut backend = OfflineBackend::default();
+    typed(&mut backend, "aiu");
+
+    let snapshot = backend
+        .process_key(vec![
+            Kind::MoveCursor(-1),
+            Kind::RemoveText(RemoveText {}),
+            Kind::AppendText("ka".to_string()),
+        ])
+        .unwrap();
+    assert_eq!((snapshot.spell.as_str(), snapshot.cursor), ("あかう", 2));
+
+    let snapshot = backend.process_key(vec![Kind::MoveCursor(-5)]).unwrap();
+    assert_eq!(snapshot.cursor, 0);
+
+    let snapshot = backend.process_key(vec![Kind::ShrinkText(1)]).unwrap();
+    assert_eq!((snapshot.spell.as_str(), snapshot.cursor), ("かう", 0));
+}
+
+#[test]
+fn scripts() {
+    let mut backend = OfflineBackend::default();
+    typed(&mut backend, "gakkou");
+
+    let preview = |backend: &mut OfflineBackend, script: Script| {
+        let snapshot = backend
+            .process_key(vec![Kind::ConvertScript(script as i32)])
+            .unwrap();
+        assert_eq!(snapshot.spell, "がっこう");
+        snapshot.preview
+    };
+    assert_eq!(preview(&mut backend, Script::Katakana), "ガッコウ");
+    assert_eq!(preview(&mut backend, Script::HalfKatakana), "ｶﾞｯｺｳ");
+    assert_eq!(preview(&mut backend, Script::HalfAlphanumeric), "gakkou");
+    assert_eq!(
+        preview(&mut backend, Script::FullAlphanumeric),
+        "ｇａｋｋｏｕ"
+    );
+
+    // typing shows the reading again
+    assert_eq!(typed(&mut backend, "n").0, "がっこうn");
+}
+
+#[test]
+fn reconversion() {
+    let mut backend = OfflineBackend::default();
+    typed(&mut backend, "ka");
+
+    let snapshot = backend.reverse_lookup("カタカナ").unwrap();
+    assert_eq!((snapshot.spell.as_str(), snapshot.cursor), ("かたかな", 4));
+
+    // there is no dictionary for the reading of kanji, and the text is kept
+    assert!(backend.reverse_lookup("漢字").is_err());
+    assert_eq!(typed(&mut backend, "").0, "かたかな");
+}
@@ -0,0 +1,222 @@
+// the incremental romaji conversion with the default table, and the table files
+
+use ime_engine::roman2kana::{to_hiragana, Layout, Replace, Romaji, Table};
+
+// the confirmed kana and the pending romaji after each key
+fn steps(table: &Table, romaji: &str) -> Vec<(String, String)> {
+    let mut converter = Romaji::default();
+    romaji
+        .chars()
+        .map(|c| {
+            converter.push(table, c);
+            (
+                converter.confirmed().to_string(),
+                converter.pending().to_string(),
+            )
+        })
+        .collect()
+}
+
+fn step(confirmed: &str, pending: &str) -> (String, String) {
+    (confirmed.to_string(), pending.to_string())
+}
+
+#[test]
+fn romaji() {
+    let table = Table::default();
+    for (romaji, kana) in [
+        ("kyouha", "きょうは"),
+        ("shinbun", "しんぶん"),
+        ("tsukue", "つくえ"),
+        ("xtu", "っ"),
+        ("qwerty", "くぇrty"),
+        ("A", "A"),
+    ] {
+        assert_eq!(to_hiragana(&table, romaji), kana, "{romaji}");
+    }
+}
+
+#[test]
+fn n() {
+    let table = Table::default();
+    for (romaji, kana) in [
+        ("nn", "ん"),
+        ("nna", "んあ"),
+        ("n'a", "んあ"),
+        ("xn", "ん"),
+        ("kanji", "かんじ"),
+        ("kannji", "かんじ"),
+        ("nya", "にゃ"),
+        ("hon", "ほん"),
+        ("honya", "ほにゃ"),
+        ("hon'ya", "ほんや"),
+    ] {
+        assert_eq!(to_hiragana(&table, romaji), kana, "{romaji}");
+    }
+
+    // "n" waits until the next key tells whether it is ん
+    assert_eq!(
+        steps(&table, "nak"),
+        [step("", "n"), step("な", ""), step("な", "k")]
+    );
+    assert_eq!(steps(&table, "nk"), [step("", "n"), step("ん", "k")]);
+}
+
+#[test]
+fn sokuon() {
+    let table = Table::default();
+    for (romaji, kana) in [
+        ("kitte", "きって"),
+        ("zasshi", "ざっし"),
+        ("macchi", "まっち"),
+        ("matchi", "まっち"),
+        ("kkk", "っっk"),
+    ] {
+        assert_eq!(to_hiragana(&table, romaji), kana, "{romaji}");
+    }
+
+    assert_eq!(
+        steps(&table, "tta"),
+        [step("", "t"), step("っ", "t"), step("った", "")]
+    );
+}
+
+#[test]
+fn ambiguous() {
+    let table = Table::default();
+
+    // the romaji which no rule continues is confirmed as it is, and the key starts over
+    assert_eq!(steps(&table, "kq"), [step("", "k"), step("k", "q")]);
+    assert_eq!(
+        steps(&table, "ky1"),
+        [step("", "k"), step("", "ky"), step("ky1", "")]
+    );
+
+    let mut converter = Romaji::default();
+    converter.push(&table, 's');
+    converter.push(&table, 'h');
+    assert!(converter.pop());
+    assert_eq!(converter.pending(), "s");
+    converter.flush(&table);
+    assert_eq!((converter.confirmed(), converter.pending()), ("s", ""));
+    assert!(!converter.pop());
+}
+
+#[test]
+fn tables() {
+    let table = Table::parse("# comment\n\nka\tカ\nkk\tッ\tk\n").unwrap();
+    assert_eq!(to_hiragana(&table, "kka"), "ッカ");
+    assert_eq!(to_hiragana(&table, "sa"), "sa");
+
+    let error = Table::parse("ka\tか\nka\tカ\nk\n\tあ\nkk\tっ\tkk\n")
+        .unwrap_err()
+        .to_string();
+    for expected in [
+        "line 2: \"ka\" is defined twice",
+        "line 3: expected 2 or 3 columns",
+        "line 4: empty romaji",
+        "line 5: \"kk\" is not shorter than \"kk\"",
+    ] {
+        assert!(error.contains(expected), "{error}");
+    }
+}
+
+#[test]
+fn azik() {
+    let table = Table::preset("azik").unwrap();
+    for (romaji, kana) in [
+        // the nasal extension: z, k, j, d, l
+        ("kz", "かん"),
+        ("kk", "きん"),
+        ("kj", "くん"),
+        ("kd", "けん"),
+        ("kl", "こん"),
+        ("kyz", "きゃん"),
+        ("nz", "なん"),
+        // the diphthong extension: q, h, w, p
+        ("kq", "かい"),
+        ("kh", "くう"),
+        ("kw", "けい"),
+        ("kp", "こう"),
+        ("sh", "すう"),
+        // "x" is "sh"
+        ("xa", "しゃ"),
+        ("xi", "し"),
+        ("xp", "しょう"),
+        // single keys
+        (";", "っ"),
+        ("q", "ん"),
+        ("ka;ta", "かった"),
+        ("nn", "ん"),
+        // the standard romaji which the extensions don't take
+        ("kyouha", "きょうは"),
+        ("tsukue", "つくえ"),
+        ("zassi", "ざっし"),
+    ] {
+        assert_eq!(to_hiragana(&table, romaji), kana, "{romaji}");
+    }
+}
+
+#[test]
+fn act() {
+    let table = Table::preset("act").unwrap();
+    for (romaji, kana) in [
+        // "c" is the k row
+        ("ca", "か"),
+        ("ci", "き"),
+        ("cyo", "きょ"),
+        // the nasal extension: n, d, h, t, s
+        ("cn", "かん"),
+        ("cd", "きん"),
+        ("ch", "くん"),
+        ("ct", "けん"),
+        ("cs", "こん"),
+        ("nn", "なん"),
+        // the diphthong extension: c, g, r, l
+        ("cc", "かい"),
+        ("cg", "けい"),
+        ("cr", "こう"),
+        ("cl", "くう"),
+        // single keys
+        (";", "っ"),
+        ("q", "ん"),
+        ("ka;ta", "かった"),
+        // the standard romaji which the extensions don't take
+        ("kyouha", "きょうは"),
+        ("tu", "つ"),
+        ("kippu", "きっぷ"),
+    ] {
+        assert_eq!(to_hiragana(&table, romaji), kana, "{romaji}");
+    }
+
+    assert!(Table::preset("qwerty").is_err());
+}
+
+#[test]
+fn layout() {
+    let mut layout = Layout::preset("azik").unwrap();
+
+    // the pending romaji is sent as it is, and replaced by the kana
+    let replace = |remove: usize, text: &str| Replace {
+        remove,
+        text: text.to_string(),
+    };
+    assert_eq!(layout.input("k"), replace(0, "k"));
+    assert_eq!(layout.input("z"), replace(1, "かん"));
+    assert_eq!(layout.input("ky"), replace(0, "ky"));
+    assert_eq!(layout.input("a"), replace(2, "きゃ"));
+    assert_eq!(layout.input("t"), replace(0, "t"));
+    layout.backspace();
+    assert_eq!(layout.input("n"), replace(0, "n"));
+    assert_eq!(layout.flush(), Some(replace(1, "ん")));
+    assert_eq!(layout.flush(), None);
+
+    // the standard table is converted on the client as well
+    let mut layout = Layout::preset("standard").unwrap();
+    assert_eq!(layout.input("ho"), replace(0, "ほ"));
+    assert_eq!(layout.input("n"), replace(0, "n"));
+    assert_eq!(layout.input("k"), replace(1, "んk"));
+    // the "k" without a rule is left in the spell, and no longer pending
+    assert_eq!(layout.flush(), None);
+    assert_eq!(layout.input("a"), replace(0, "あ"));
+}
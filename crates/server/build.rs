@@ -1,6 +1,11 @@
 use std::env;
 
 fn main() {
+    // the swift library is only needed by the ffi converter
+    if env::var_os("CARGO_FEATURE_FFI").is_none() {
+        return;
+    }
+
     // link dll
     let project_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
     println!("cargo:rustc-link-search={}/target/", project_dir);
@@ -0,0 +1,47 @@
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod stub;
+
+use protos::proto::{InputStyle, Suggestion};
+
+#[derive(Debug, Default, Clone)]
+pub struct RawComposingText {
+    pub text: String,
+    pub cursor: i8,
+}
+
+// backend of the kana-kanji conversion
+// the server only talks to this trait, so the swift library can be replaced with a stub
+pub trait Converter: Send {
+    fn append_text(&mut self, input: &str, style: InputStyle) -> RawComposingText;
+    fn remove_text(&mut self) -> RawComposingText;
+    fn move_cursor(&mut self, offset: i8) -> RawComposingText;
+    fn clear_text(&mut self);
+    fn candidates(&mut self) -> Vec<Suggestion>;
+    fn shrink_text(&mut self, offset: i8) -> RawComposingText;
+    // keys typed by the user before roman2kana, used for the alphanumeric conversions
+    fn raw_input(&mut self) -> String;
+    // suggestions for a part of the reading, converted as a single segment
+    fn segment_candidates(&mut self, reading: &str) -> Vec<Suggestion>;
+    // length of the first clause of the reading, counted in characters
+    fn first_clause(&mut self, reading: &str) -> usize;
+    // reading of a committed text in hiragana, for the reconversion
+    // None if the reading is unknown, the text is never taken as its own reading
+    // the dictionary of azookey is indexed by the reading, so only kana is known for now
+    fn reading(&mut self, text: &str) -> Option<String>;
+}
+
+// stable id of a candidate, it doesn't change across sessions and server restarts
+// so that the client can refer to the same candidate later (e.g. for learning)
+pub fn candidate_id(text: &str, reading: &str) -> u64 {
+    // FNV-1a
+    const OFFSET: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    let mut hash = OFFSET;
+    for byte in text.bytes().chain([0]).chain(reading.bytes()) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(PRIME);
+    }
+    hash
+}
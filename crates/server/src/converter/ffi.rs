@@ -0,0 +1,230 @@
+use std::ffi::{c_char, c_int, CStr, CString};
+
+use protos::proto::{CandidateSource, InputStyle, Suggestion};
+
+use super::{candidate_id, Converter, RawComposingText};
+
+#[derive(Debug, Clone)]
+#[repr(C)]
+struct FFICandidate {
+    text: *mut c_char,
+    subtext: *mut c_char,
+    reading: *mut c_char,
+    // empty if the candidate has no note
+    annotation: *mut c_char,
+    corresponding_count: c_int,
+    // FFICandidateSource of ffi.h, which has the values of CandidateSource
+    source: c_int,
+}
+
+extern "C" {
+    fn Initialize(path: *const c_char);
+    fn CreateSession() -> c_int;
+    fn DestroySession(session: c_int);
+    fn AppendText(
+        session: c_int,
+        input: *const c_char,
+        kana: bool,
+        cursorPtr: *mut c_int,
+    ) -> *mut c_char;
+    fn RemoveText(session: c_int, cursorPtr: *mut c_int) -> *mut c_char;
+    fn MoveCursor(session: c_int, offset: c_int, cursorPtr: *mut c_int) -> *mut c_char;
+    fn ClearText(session: c_int);
+    fn GetComposedText(session: c_int, lengthPtr: *mut c_int) -> *mut *mut FFICandidate;
+    fn ShrinkText(session: c_int, offset: c_int, cursorPtr: *mut c_int) -> *mut c_char;
+    fn GetRawInput(session: c_int) -> *mut c_char;
+    fn GetSegmentCandidates(
+        reading: *const c_char,
+        lengthPtr: *mut c_int,
+    ) -> *mut *mut FFICandidate;
+    fn GetFirstClause(reading: *const c_char) -> c_int;
+    fn GetReading(text: *const c_char) -> *mut c_char;
+    fn FreeString(ptr: *mut c_char);
+    fn FreeCandidates(list: *mut *mut FFICandidate, length: c_int);
+}
+
+// path is the directory which contains the dictionaries
+// this has to be called once before creating any converter
+pub fn initialize(path: &str) {
+    unsafe {
+        let path = CString::new(path).expect("CString::new failed");
+        Initialize(path.as_ptr());
+    }
+}
+
+// converter backed by azookey-server.lib (AzooKeyKanaKanjiConverter)
+// each instance owns its own composing text on the swift side
+#[derive(Debug)]
+pub struct FFIConverter {
+    session: c_int,
+}
+
+impl FFIConverter {
+    pub fn open() -> Self {
+        let session = unsafe { CreateSession() };
+
+        Self { session }
+    }
+}
+
+impl Drop for FFIConverter {
+    fn drop(&mut self) {
+        unsafe {
+            DestroySession(self.session);
+        }
+    }
+}
+
+impl Converter for FFIConverter {
+    fn append_text(&mut self, input: &str, style: InputStyle) -> RawComposingText {
+        unsafe {
+            let input = CString::new(input).expect("CString::new failed");
+            let kana = style == InputStyle::Kana;
+            let mut cursor: c_int = 0;
+
+            let result = AppendText(self.session, input.as_ptr(), kana, &mut cursor);
+
+            RawComposingText {
+                text: take_string(result),
+                cursor: cursor as i8,
+            }
+        }
+    }
+
+    fn remove_text(&mut self) -> RawComposingText {
+        unsafe {
+            let mut cursor: c_int = 0;
+
+            let result = RemoveText(self.session, &mut cursor);
+
+            RawComposingText {
+                text: take_string(result),
+                cursor: cursor as i8,
+            }
+        }
+    }
+
+    fn move_cursor(&mut self, offset: i8) -> RawComposingText {
+        unsafe {
+            let offset = c_int::from(offset);
+            let mut cursor: c_int = 0;
+
+            let result = MoveCursor(self.session, offset, &mut cursor);
+
+            RawComposingText {
+                text: take_string(result),
+                cursor: cursor as i8,
+            }
+        }
+    }
+
+    fn clear_text(&mut self) {
+        unsafe {
+            ClearText(self.session);
+        }
+    }
+
+    fn candidates(&mut self) -> Vec<Suggestion> {
+        unsafe {
+            let mut length: c_int = 0;
+            let result = GetComposedText(self.session, &mut length);
+
+            suggestions(result, length)
+        }
+    }
+
+    fn shrink_text(&mut self, offset: i8) -> RawComposingText {
+        unsafe {
+            let offset = c_int::from(offset);
+            let mut cursor: c_int = 0;
+
+            let result = ShrinkText(self.session, offset, &mut cursor);
+
+            RawComposingText {
+                text: take_string(result),
+                cursor: cursor as i8,
+            }
+        }
+    }
+
+    fn segment_candidates(&mut self, reading: &str) -> Vec<Suggestion> {
+        unsafe {
+            let reading = CString::new(reading).expect("CString::new failed");
+            let mut length: c_int = 0;
+            let result = GetSegmentCandidates(reading.as_ptr(), &mut length);
+
+            suggestions(result, length)
+        }
+    }
+
+    fn first_clause(&mut self, reading: &str) -> usize {
+        unsafe {
+            let reading = CString::new(reading).expect("CString::new failed");
+
+            GetFirstClause(reading.as_ptr()).max(0) as usize
+        }
+    }
+
+    fn reading(&mut self, text: &str) -> Option<String> {
+        unsafe {
+            let text = CString::new(text).expect("CString::new failed");
+            let result = GetReading(text.as_ptr());
+
+            (!result.is_null()).then(|| take_string(result))
+        }
+    }
+
+    fn raw_input(&mut self) -> String {
+        unsafe {
+            let result = GetRawInput(self.session);
+
+            take_string(result)
+        }
+    }
+}
+
+// copy a string returned by the swift library, which is freed on its side
+unsafe fn take_string(ptr: *mut c_char) -> String {
+    let text = CStr::from_ptr(ptr).to_string_lossy().into_owned();
+    FreeString(ptr);
+    text
+}
+
+// read the candidates returned by the swift library, and free them
+unsafe fn suggestions(result: *mut *mut FFICandidate, length: c_int) -> Vec<Suggestion> {
+    let mut suggestions = Vec::with_capacity(length as usize);
+
+    for index in 0..length as usize {
+        let candidate = (**result.add(index)).clone();
+        let text = CStr::from_ptr(candidate.text)
+            .to_string_lossy()
+            .into_owned();
+        let subtext = CStr::from_ptr(candidate.subtext)
+            .to_string_lossy()
+            .into_owned();
+        let reading = CStr::from_ptr(candidate.reading)
+            .to_string_lossy()
+            .into_owned();
+        let annotation = CStr::from_ptr(candidate.annotation)
+            .to_string_lossy()
+            .into_owned();
+        let corresponding_count = candidate.corresponding_count;
+
+        let suggestion = Suggestion {
+            id: candidate_id(&text, &reading),
+            text,
+            subtext,
+            corresponding_count,
+            reading,
+            annotation,
+            source: CandidateSource::try_from(candidate.source)
+                .unwrap_or(CandidateSource::Unspecified) as i32,
+        };
+
+        suggestions.push(suggestion);
+    }
+
+    FreeCandidates(result, length);
+
+    suggestions
+}
@@ -0,0 +1,167 @@
+use ime_engine::{
+    kana::{is_kana, to_hiragana},
+    roman2kana::{Romaji, Table},
+};
+use protos::proto::{CandidateSource, InputStyle, Suggestion};
+
+use super::{candidate_id, Converter, RawComposingText};
+
+// (reading, word) of the only words which the stub converts into kanji
+const WORDS: [(&str, &str); 2] = [("かんじ", "漢字"), ("へんかん", "変換")];
+
+// deterministic in-memory converter
+// the romaji is turned into kana by the standard table as azookey does, but the kana-kanji
+// conversion only knows WORDS, so the server can be built and tested without the swift toolchain
+#[derive(Debug, Default)]
+pub struct StubConverter {
+    table: Table,
+    // kana before and after the cursor, the pending romaji is between them
+    text: Vec<char>,
+    cursor: usize,
+    romaji: Romaji,
+}
+
+impl StubConverter {
+    fn chars(&self) -> Vec<char> {
+        let pending = self.romaji.pending().chars();
+        let mut chars = self.text[..self.cursor].to_vec();
+        chars.extend(pending);
+        chars.extend_from_slice(&self.text[self.cursor..]);
+        chars
+    }
+
+    fn composing_text(&self) -> RawComposingText {
+        RawComposingText {
+            text: self.chars().iter().collect(),
+            cursor: (self.cursor + self.romaji.pending().chars().count()) as i8,
+        }
+    }
+
+    fn insert(&mut self, text: &str) {
+        for c in text.chars() {
+            self.text.insert(self.cursor, c);
+            self.cursor += 1;
+        }
+    }
+
+    // the pending romaji is only kept at the end of typing
+    fn flush(&mut self) {
+        self.romaji.flush(&self.table);
+        let confirmed = self.romaji.take_confirmed();
+        self.insert(&confirmed);
+    }
+}
+
+impl Converter for StubConverter {
+    // the kana input style types kana, which is kept as it is
+    fn append_text(&mut self, input: &str, style: InputStyle) -> RawComposingText {
+        for c in input.chars() {
+            if style == InputStyle::Romaji && c.is_ascii() {
+                self.romaji.push(&self.table, c);
+            } else {
+                self.flush();
+                self.insert(&c.to_string());
+            }
+            let confirmed = self.romaji.take_confirmed();
+            self.insert(&confirmed);
+        }
+
+        self.composing_text()
+    }
+
+    fn remove_text(&mut self) -> RawComposingText {
+        if !self.romaji.pop() && self.cursor > 0 {
+            self.cursor -= 1;
+            self.text.remove(self.cursor);
+        }
+
+        self.composing_text()
+    }
+
+    fn move_cursor(&mut self, offset: i8) -> RawComposingText {
+        self.flush();
+        let cursor = self.cursor as i64 + offset as i64;
+        self.cursor = cursor.clamp(0, self.text.len() as i64) as usize;
+
+        self.composing_text()
+    }
+
+    fn clear_text(&mut self) {
+        self.text.clear();
+        self.cursor = 0;
+        self.romaji = Romaji::default();
+    }
+
+    // the whole text comes first, followed by every shorter prefix
+    // so that prefix commits (ShrinkText) can be exercised
+    fn candidates(&mut self) -> Vec<Suggestion> {
+        let chars = self.chars();
+
+        (1..=chars.len())
+            .rev()
+            .flat_map(|count| {
+                let reading: String = chars[..count].iter().collect();
+                let subtext: String = chars[count..].iter().collect();
+
+                words(&reading).into_iter().map(move |text| Suggestion {
+                    id: candidate_id(&text, &reading),
+                    reading: reading.clone(),
+                    subtext: subtext.clone(),
+                    corresponding_count: count as i32,
+                    source: CandidateSource::SystemDictionary as i32,
+                    annotation: String::new(),
+                    text,
+                })
+            })
+            .collect()
+    }
+
+    // the input elements, which are the kana itself once it is resolved
+    fn raw_input(&mut self) -> String {
+        self.chars().iter().collect()
+    }
+
+    fn segment_candidates(&mut self, reading: &str) -> Vec<Suggestion> {
+        words(reading)
+            .into_iter()
+            .map(|text| Suggestion {
+                id: candidate_id(&text, reading),
+                text,
+                subtext: String::new(),
+                corresponding_count: reading.chars().count() as i32,
+                reading: reading.to_string(),
+                source: CandidateSource::SystemDictionary as i32,
+                annotation: String::new(),
+            })
+            .collect()
+    }
+
+    // clauses of 2 characters, so that the segments are predictable
+    fn first_clause(&mut self, reading: &str) -> usize {
+        reading.chars().count().min(2)
+    }
+
+    // only the kana itself, as azookey can't look the words up by their text either
+    fn reading(&mut self, text: &str) -> Option<String> {
+        is_kana(text).then(|| to_hiragana(text))
+    }
+
+    fn shrink_text(&mut self, offset: i8) -> RawComposingText {
+        self.flush();
+        let offset = (offset.max(0) as usize).min(self.text.len());
+        self.text.drain(..offset);
+        self.cursor = self.cursor.saturating_sub(offset);
+
+        self.composing_text()
+    }
+}
+
+// the word of the reading if any, followed by the reading itself
+fn words(reading: &str) -> Vec<String> {
+    WORDS
+        .iter()
+        .filter(|(word_reading, _)| *word_reading == reading)
+        .map(|(_, word)| word.to_string())
+        .chain([reading.to_string()])
+        .collect()
+}
@@ -0,0 +1,4 @@
+// the conversion logic of the server, shared with the tests of ime-engine
+pub mod converter;
+pub mod session;
+pub mod window;
@@ -3,164 +3,150 @@ use tonic_reflection::server::Builder as ReflectionBuilder;
 
 use protos::proto::azookey_service_server::{AzookeyService, AzookeyServiceServer};
 use protos::proto::{
-    AppendTextRequest, AppendTextResponse, ClearTextRequest, ClearTextResponse, ComposingText,
-    MoveCursorRequest, MoveCursorResponse, RemoveTextRequest, RemoveTextResponse,
-    ShrinkTextRequest, ShrinkTextResponse, Suggestion,
+    action::{ClearText, Kind, RemoveText},
+    AppendTextRequest, AppendTextResponse, ClearTextRequest, ClearTextResponse,
+    CloseSessionRequest, CloseSessionResponse, ComposingText, CompositionSnapshot,
+    MoveCursorRequest, MoveCursorResponse, OpenSessionRequest, OpenSessionResponse,
+    ProcessKeyRequest, ProcessKeyResponse, RemoveTextRequest, RemoveTextResponse,
+    ReverseLookupRequest, ReverseLookupResponse, SegmentCandidatesRequest,
+    SegmentCandidatesResponse, ShrinkTextRequest, ShrinkTextResponse,
 };
 
-use std::ffi::{c_char, c_int, CStr, CString};
+use azookey_server::{
+    converter::{self, Converter},
+    session::Session,
+    window::CandidateWindow,
+};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
 
-struct RawComposingText {
-    text: String,
-    cursor: i8,
-}
+type ConverterFactory = Box<dyn Fn() -> Box<dyn Converter> + Send + Sync>;
 
-#[derive(Debug, Clone)]
-#[repr(C)]
-struct FFICandidate {
-    text: *mut c_char,
-    subtext: *mut c_char,
-    corresponding_count: c_int,
+pub struct MyAzookeyService {
+    // composing state of each client, keyed by session id
+    sessions: Mutex<HashMap<u64, Session>>,
+    next_session_id: AtomicU64,
+    new_converter: ConverterFactory,
+    window: CandidateWindow,
 }
 
-extern "C" {
-    fn Initialize(path: *const c_char);
-    fn AppendText(input: *const c_char, cursorPtr: *mut c_int) -> *mut c_char;
-    fn RemoveText(cursorPtr: *mut c_int) -> *mut c_char;
-    fn MoveCursor(offset: c_int, cursorPtr: *mut c_int) -> *mut c_char;
-    fn ClearText();
-    fn GetComposedText(lengthPtr: *mut c_int) -> *mut *mut FFICandidate;
-    fn ShrinkText(offset: c_int);
-}
-
-fn initialize(path: &str) {
-    unsafe {
-        let path = CString::new(path).expect("CString::new failed");
-        Initialize(path.as_ptr());
-    }
-}
-
-fn add_text(input: &str) -> RawComposingText {
-    unsafe {
-        let input = CString::new(input).expect("CString::new failed");
-        let mut cursor: c_int = 0;
-
-        let result = AppendText(input.as_ptr(), &mut cursor);
-
-        let text = CStr::from_ptr(&*result as *const c_char).to_str().unwrap();
-
-        RawComposingText {
-            text: text.to_string(),
-            cursor: cursor as i8,
+impl MyAzookeyService {
+    pub fn new(new_converter: ConverterFactory, window: CandidateWindow) -> Self {
+        Self {
+            sessions: Mutex::new(HashMap::new()),
+            // 0 is never used, so that a client which forgot to open a session is rejected
+            next_session_id: AtomicU64::new(1),
+            new_converter,
+            window,
         }
     }
-}
-
-fn move_cursor(offset: i8) -> RawComposingText {
-    unsafe {
-        let offset = c_int::from(offset);
-        println!("Offset: {}", offset);
-        let mut cursor: c_int = 0;
 
-        let result = MoveCursor(offset, &mut cursor);
-
-        let text = CStr::from_ptr(&*result as *const c_char).to_str().unwrap();
-
-        RawComposingText {
-            text: text.to_string(),
-            cursor: cursor as i8,
-        }
+    fn with_session<T>(&self, session_id: u64, f: impl FnOnce(&mut Session) -> T) -> Option<T> {
+        // a panic inside a request does not break the converters themselves, so keep using them
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        sessions.get_mut(&session_id).map(f)
     }
-}
-
-fn remove_text() -> RawComposingText {
-    unsafe {
-        let mut cursor: c_int = 0;
-
-        let result = RemoveText(&mut cursor);
 
-        let text = CStr::from_ptr(&*result as *const c_char).to_str().unwrap();
-
-        RawComposingText {
-            text: text.to_string(),
-            cursor: cursor as i8,
-        }
+    // apply the actions, and return the resulting snapshot
+    fn process(&self, session_id: u64, actions: Vec<Kind>) -> Option<CompositionSnapshot> {
+        self.with_session(session_id, |session| {
+            for action in actions {
+                session.apply(action);
+            }
+            session.snapshot()
+        })
     }
 }
 
-fn clear_text() {
-    unsafe {
-        ClearText();
-    }
+fn session_not_found(session_id: u64) -> Status {
+    Status::not_found(format!("Session {} is not opened", session_id))
 }
 
-fn get_composed_text() -> Vec<Suggestion> {
-    unsafe {
-        let mut length: c_int = 0;
-        let result = GetComposedText(&mut length);
-        let mut suggestions = Vec::with_capacity(length as usize);
-
-        for index in 0..length as usize {
-            let candidate = (**result.add(index)).clone();
-            let text = CStr::from_ptr(candidate.text)
-                .to_string_lossy()
-                .into_owned();
-            let subtext = CStr::from_ptr(candidate.subtext)
-                .to_string_lossy()
-                .into_owned();
-            let corresponding_count = candidate.corresponding_count;
-
-            let suggestion = Suggestion {
-                text,
-                subtext,
-                corresponding_count,
-            };
-
-            suggestions.push(suggestion);
-        }
-
-        suggestions
-    }
+// the legacy rpcs return only a part of the snapshot
+fn composing_text(snapshot: CompositionSnapshot) -> Option<ComposingText> {
+    Some(ComposingText {
+        spell: snapshot.spell,
+        suggestions: snapshot.suggestions,
+        cursor: snapshot.cursor,
+    })
 }
 
-fn shrink_text(offset: i8) {
-    unsafe {
-        let offset = c_int::from(offset);
-        ShrinkText(offset);
+#[tonic::async_trait]
+impl AzookeyService for MyAzookeyService {
+    async fn open_session(
+        &self,
+        request: Request<OpenSessionRequest>,
+    ) -> Result<Response<OpenSessionResponse>, Status> {
+        let request = request.into_inner();
+        let session_id = self.next_session_id.fetch_add(1, Ordering::Relaxed);
+        let converter = (self.new_converter)();
+
+        self.sessions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(
+                session_id,
+                Session::new(
+                    converter,
+                    request.page_size,
+                    request.conversion_style(),
+                    request.input_style(),
+                ),
+            );
+        println!("Session {} opened", session_id);
+
+        Ok(Response::new(OpenSessionResponse { session_id }))
     }
-}
 
-#[derive(Debug, Default)]
-pub struct MyAzookeyService;
+    async fn close_session(
+        &self,
+        request: Request<CloseSessionRequest>,
+    ) -> Result<Response<CloseSessionResponse>, Status> {
+        let session_id = request.into_inner().session_id;
+
+        self.sessions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&session_id)
+            .ok_or_else(|| session_not_found(session_id))?;
+        println!("Session {} closed", session_id);
+
+        Ok(Response::new(CloseSessionResponse {}))
+    }
 
-#[tonic::async_trait]
-impl AzookeyService for MyAzookeyService {
     async fn append_text(
         &self,
         request: Request<AppendTextRequest>,
     ) -> Result<Response<AppendTextResponse>, Status> {
-        let input = request.into_inner().text_to_append;
-        let composing_text = add_text(&input);
+        let request = request.into_inner();
+        let snapshot = self
+            .process(
+                request.session_id,
+                vec![Kind::AppendText(request.text_to_append)],
+            )
+            .ok_or_else(|| session_not_found(request.session_id))?;
 
         Ok(Response::new(AppendTextResponse {
-            composing_text: Some(ComposingText {
-                spell: composing_text.text,
-                suggestions: get_composed_text().to_vec(),
-            }),
+            composing_text: composing_text(snapshot),
         }))
     }
 
     async fn remove_text(
         &self,
-        _: Request<RemoveTextRequest>,
+        request: Request<RemoveTextRequest>,
     ) -> Result<Response<RemoveTextResponse>, Status> {
-        let composing_text = remove_text();
+        let session_id = request.into_inner().session_id;
+        let snapshot = self
+            .process(session_id, vec![Kind::RemoveText(RemoveText {})])
+            .ok_or_else(|| session_not_found(session_id))?;
 
         Ok(Response::new(RemoveTextResponse {
-            composing_text: Some(ComposingText {
-                spell: composing_text.text,
-                suggestions: get_composed_text().to_vec(),
-            }),
+            composing_text: composing_text(snapshot),
         }))
     }
 
@@ -168,22 +154,24 @@ impl AzookeyService for MyAzookeyService {
         &self,
         request: Request<MoveCursorRequest>,
     ) -> Result<Response<MoveCursorResponse>, Status> {
-        let offset = request.into_inner().offset as i8;
-        let composing_text = move_cursor(offset);
+        let request = request.into_inner();
+        let snapshot = self
+            .process(request.session_id, vec![Kind::MoveCursor(request.offset)])
+            .ok_or_else(|| session_not_found(request.session_id))?;
 
         Ok(Response::new(MoveCursorResponse {
-            composing_text: Some(ComposingText {
-                spell: composing_text.text,
-                suggestions: get_composed_text().to_vec(),
-            }),
+            composing_text: composing_text(snapshot),
         }))
     }
 
     async fn clear_text(
         &self,
-        _: Request<ClearTextRequest>,
+        request: Request<ClearTextRequest>,
     ) -> Result<Response<ClearTextResponse>, Status> {
-        clear_text();
+        let session_id = request.into_inner().session_id;
+        self.process(session_id, vec![Kind::ClearText(ClearText {})])
+            .ok_or_else(|| session_not_found(session_id))?;
+
         Ok(Response::new(ClearTextResponse {}))
     }
 
@@ -191,28 +179,113 @@ impl AzookeyService for MyAzookeyService {
         &self,
         request: Request<ShrinkTextRequest>,
     ) -> Result<Response<ShrinkTextResponse>, Status> {
-        let offset = request.into_inner().offset as i8;
-        shrink_text(offset);
+        let request = request.into_inner();
+        let snapshot = self
+            .process(request.session_id, vec![Kind::ShrinkText(request.offset)])
+            .ok_or_else(|| session_not_found(request.session_id))?;
 
         Ok(Response::new(ShrinkTextResponse {
-            composing_text: Some(ComposingText {
-                spell: "".to_string(),
-                suggestions: get_composed_text().to_vec(),
-            }),
+            composing_text: composing_text(snapshot),
+        }))
+    }
+
+    async fn process_key(
+        &self,
+        request: Request<ProcessKeyRequest>,
+    ) -> Result<Response<ProcessKeyResponse>, Status> {
+        let request = request.into_inner();
+        let actions: Vec<Kind> = request
+            .actions
+            .into_iter()
+            .filter_map(|action| action.kind)
+            .collect();
+        let (snapshot, update) = self
+            .with_session(request.session_id, |session| {
+                for action in actions {
+                    session.apply(action);
+                }
+                let snapshot = session.snapshot();
+                let update = session.window_update(&snapshot);
+                (snapshot, update)
+            })
+            .ok_or_else(|| session_not_found(request.session_id))?;
+
+        // the server updates the candidate window by itself, so the client needs only one round trip
+        self.window.update(update);
+
+        Ok(Response::new(ProcessKeyResponse {
+            snapshot: Some(snapshot),
         }))
     }
+
+    async fn segment_candidates(
+        &self,
+        request: Request<SegmentCandidatesRequest>,
+    ) -> Result<Response<SegmentCandidatesResponse>, Status> {
+        let request = request.into_inner();
+        let start = request.start.max(0) as usize;
+        let length = request.length.max(0) as usize;
+        let suggestions = self
+            .with_session(request.session_id, |session| {
+                session.span_candidates(start, length)
+            })
+            .ok_or_else(|| session_not_found(request.session_id))?;
+
+        Ok(Response::new(SegmentCandidatesResponse { suggestions }))
+    }
+
+    async fn reverse_lookup(
+        &self,
+        request: Request<ReverseLookupRequest>,
+    ) -> Result<Response<ReverseLookupResponse>, Status> {
+        let request = request.into_inner();
+        let (reading, snapshot, update) = self
+            .with_session(request.session_id, |session| {
+                let reading = session.reverse_lookup(&request.text)?;
+                let snapshot = session.snapshot();
+                let update = session.window_update(&snapshot);
+                Some((reading, snapshot, update))
+            })
+            .ok_or_else(|| session_not_found(request.session_id))?
+            .ok_or_else(|| {
+                Status::unimplemented(format!("The reading of {:?} is unknown", request.text))
+            })?;
+
+        self.window.update(update);
+
+        Ok(Response::new(ReverseLookupResponse {
+            reading,
+            snapshot: Some(snapshot),
+        }))
+    }
+}
+
+// the stub converter is used when the swift library is not linked, or `--stub` is passed
+fn converter_factory() -> Result<ConverterFactory, Box<dyn std::error::Error>> {
+    #[cfg(feature = "ffi")]
+    if !std::env::args().any(|arg| arg == "--stub") {
+        // get executable directory
+        let current_exe = std::env::current_exe()?;
+        let parent_dir = current_exe.parent().unwrap();
+        converter::ffi::initialize(parent_dir.to_str().unwrap());
+
+        return Ok(Box::new(|| Box::new(converter::ffi::FFIConverter::open())));
+    }
+
+    println!("Using the stub converter");
+    Ok(Box::new(|| {
+        Box::new(converter::stub::StubConverter::default())
+    }))
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("AzookeyServer started");
-    // get executable directory
-    let current_exe = std::env::current_exe()?;
-    let parent_dir = current_exe.parent().unwrap();
-    initialize(parent_dir.to_str().unwrap());
+    let new_converter = converter_factory()?;
 
     let addr = "[::1]:50051".parse()?;
-    let service = MyAzookeyService::default();
+    let window = CandidateWindow::spawn("http://[::1]:50052");
+    let service = MyAzookeyService::new(new_converter, window);
 
     println!("AzookeyServer listening on {}", addr);
 
@@ -0,0 +1,614 @@
+use protos::proto::{
+    self,
+    action::{ClearText, Kind},
+    CandidateSource, CompositionSnapshot, ConversionStyle, InputStyle, Script, Suggestion,
+};
+
+use ime_engine::script::{self, next_variant};
+
+use crate::{
+    converter::{candidate_id, Converter, RawComposingText},
+    window::WindowUpdate,
+};
+
+// 9 suggestions fit the number keys
+const DEFAULT_PAGE_SIZE: i32 = 9;
+
+// a clause of the reading, which has its own suggestions while the user edits segments
+struct Segment {
+    // length of the reading, counted in characters
+    len: usize,
+    suggestions: Vec<Suggestion>,
+    selection: i32,
+    script: Option<(Script, usize)>,
+}
+
+// composing state of a client
+pub struct Session {
+    converter: Box<dyn Converter>,
+    composing_text: RawComposingText,
+    suggestions: Vec<Suggestion>,
+    selection: i32,
+    // suggestions have to be requested again after the composing text is changed
+    outdated: bool,
+    page_size: i32,
+    // F6-F10 conversion of the reading and its variant, cleared by any other action
+    script: Option<(Script, usize)>,
+    // empty unless the user resizes segments, the reading is converted as a whole then
+    segments: Vec<Segment>,
+    focused: usize,
+    conversion_style: ConversionStyle,
+    // whether the appended text is romaji or kana
+    input_style: InputStyle,
+    // set by the selection actions, the explicit style shows the reading until then
+    converting: bool,
+    // the script of the input mode, which is not converted while typing whatever the style is
+    mode_script: Option<Script>,
+}
+
+impl Session {
+    pub fn new(
+        converter: Box<dyn Converter>,
+        page_size: u32,
+        conversion_style: ConversionStyle,
+        input_style: InputStyle,
+    ) -> Self {
+        Self {
+            converter,
+            composing_text: RawComposingText::default(),
+            suggestions: vec![],
+            selection: 0,
+            outdated: false,
+            // 0 is the default of the request, and the pages are counted in i32
+            page_size: i32::try_from(page_size)
+                .ok()
+                .filter(|page_size| *page_size > 0)
+                .unwrap_or(DEFAULT_PAGE_SIZE),
+            script: None,
+            segments: vec![],
+            focused: 0,
+            conversion_style,
+            input_style,
+            converting: false,
+            mode_script: None,
+        }
+    }
+
+    pub fn apply(&mut self, action: Kind) {
+        if !matches!(action, Kind::ConvertScript(_) | Kind::ModeScript(_)) {
+            self.script = None;
+        }
+
+        match action {
+            Kind::AppendText(text) => {
+                let style = self.input_style;
+                self.edit(|converter| converter.append_text(&text, style))
+            }
+            Kind::RemoveText(_) => self.edit(|converter| converter.remove_text()),
+            Kind::MoveCursor(offset) => {
+                self.edit(|converter| converter.move_cursor(clamp_offset(offset)))
+            }
+            Kind::ShrinkText(offset) => {
+                self.edit(|converter| converter.shrink_text(clamp_offset(offset)))
+            }
+            Kind::ClearText(_) => {
+                self.converter.clear_text();
+                self.composing_text = RawComposingText::default();
+                self.suggestions.clear();
+                self.selection = 0;
+                self.outdated = false;
+                self.segments.clear();
+                self.focused = 0;
+                self.converting = false;
+            }
+            Kind::MoveSelection(offset) => {
+                self.converting = true;
+                self.refresh();
+                let (_, selection) = self.focused_list();
+                let index = selection.saturating_add(offset);
+                self.select(index);
+            }
+            Kind::SelectSuggestion(index) => {
+                self.converting = true;
+                self.refresh();
+                self.select(index);
+            }
+            Kind::MovePage(offset) => {
+                self.converting = true;
+                self.refresh();
+                let page_size = self.page_size;
+                let (suggestions, selection) = self.focused_list();
+                let last_page = (suggestions.len() as i32 - 1).max(0) / page_size;
+                let page = (*selection / page_size)
+                    .saturating_add(offset)
+                    .clamp(0, last_page);
+                self.select(page * page_size);
+            }
+            Kind::ConvertScript(script) => {
+                let script = Script::try_from(script).unwrap_or(Script::Hiragana);
+                match self.segments.get_mut(self.focused) {
+                    // the romaji of a segment is unknown, so alphanumeric is applied to the whole reading
+                    Some(segment) if script::variant_count(script) == 1 => {
+                        segment.script = Some(next_variant(segment.script, script));
+                    }
+                    _ => {
+                        self.segments.clear();
+                        self.focused = 0;
+                        self.script = Some(next_variant(self.script, script));
+                    }
+                }
+            }
+            Kind::ResizeSegment(offset) => {
+                self.converting = true;
+                self.ensure_segments();
+                self.resize(offset);
+            }
+            Kind::MoveSegment(offset) => {
+                self.converting = true;
+                self.ensure_segments();
+                let last = self.segments.len().saturating_sub(1) as i32;
+                self.focused = (self.focused as i32).saturating_add(offset).clamp(0, last) as usize;
+            }
+            Kind::ModeScript(script) => {
+                self.mode_script = Script::try_from(script)
+                    .ok()
+                    .filter(|script| *script != Script::Unspecified);
+            }
+        }
+    }
+
+    pub fn snapshot(&mut self) -> CompositionSnapshot {
+        self.refresh();
+
+        if !self.segments.is_empty() {
+            return self.segments_snapshot();
+        }
+
+        // the reading is typed in the script of the mode until the conversion is started
+        let mode_script = self
+            .mode_script
+            .filter(|_| !self.converting)
+            .map(|script| (script, 0));
+        let (preview, suffix) = match (
+            self.script.or(mode_script),
+            self.suggestions.get(self.selection as usize),
+        ) {
+            // the whole reading is shown in the script
+            (Some((script, variant)), _) => {
+                let raw = self.converter.raw_input();
+                let preview = script::convert(script, variant, &self.composing_text.text, &raw);
+                (preview, String::new())
+            }
+            (None, Some(suggestion)) if self.shows_conversion() => {
+                (suggestion.text.clone(), suggestion.subtext.clone())
+            }
+            (None, _) => (self.composing_text.text.clone(), String::new()),
+        };
+
+        CompositionSnapshot {
+            spell: self.composing_text.text.clone(),
+            preview,
+            suffix,
+            cursor: self.composing_text.cursor as i32,
+            suggestions: self.suggestions.clone(),
+            selection: self.selection,
+            segments: vec![],
+            focused_segment: 0,
+        }
+    }
+
+    // convert the committed text again, and return its reading
+    // the text itself comes first, so that the document doesn't change until another is selected
+    // None if the reading of the text is unknown, the session is left as it is then
+    pub fn reverse_lookup(&mut self, text: &str) -> Option<String> {
+        let reading = self.converter.reading(text)?;
+        self.apply(Kind::ClearText(ClearText {}));
+        self.apply(Kind::AppendText(reading.clone()));
+        self.refresh();
+
+        self.suggestions
+            .retain(|suggestion| suggestion.text != text || !suggestion.subtext.is_empty());
+        self.suggestions.insert(
+            0,
+            Suggestion {
+                id: candidate_id(text, &reading),
+                text: text.to_string(),
+                subtext: String::new(),
+                corresponding_count: self.converter.raw_input().chars().count() as i32,
+                reading: reading.clone(),
+                source: CandidateSource::Unspecified as i32,
+                annotation: String::new(),
+            },
+        );
+        self.converting = true;
+
+        Some(reading)
+    }
+
+    // suggestions for a part of the reading, start and length are counted in characters
+    pub fn span_candidates(&mut self, start: usize, length: usize) -> Vec<Suggestion> {
+        let reading: String = self
+            .composing_text
+            .text
+            .chars()
+            .skip(start)
+            .take(length)
+            .collect();
+
+        if reading.is_empty() {
+            vec![]
+        } else {
+            self.converter.segment_candidates(&reading)
+        }
+    }
+
+    // the preview is made of the segments, and the suggestions are of the focused one
+    fn segments_snapshot(&mut self) -> CompositionSnapshot {
+        let spell = self.composing_text.text.clone();
+        let mut chars = spell.chars();
+
+        let segments: Vec<proto::Segment> = self
+            .segments
+            .iter()
+            .map(|segment| {
+                let reading: String = chars.by_ref().take(segment.len).collect();
+                let text = match (
+                    segment.script,
+                    segment.suggestions.get(segment.selection as usize),
+                ) {
+                    (Some((script, variant)), _) => {
+                        script::convert(script, variant, &reading, &reading)
+                    }
+                    (None, Some(suggestion)) => suggestion.text.clone(),
+                    (None, None) => reading.clone(),
+                };
+                proto::Segment { reading, text }
+            })
+            .collect();
+        let focused = &self.segments[self.focused];
+
+        CompositionSnapshot {
+            cursor: spell.chars().count() as i32,
+            preview: segments
+                .iter()
+                .map(|segment| segment.text.as_str())
+                .collect(),
+            suffix: String::new(),
+            suggestions: focused.suggestions.clone(),
+            selection: focused.selection,
+            spell,
+            segments,
+            focused_segment: self.focused as i32,
+        }
+    }
+
+    pub fn window_update(&self, snapshot: &CompositionSnapshot) -> WindowUpdate {
+        if snapshot.suggestions.is_empty() || !self.shows_conversion() {
+            WindowUpdate::Hide
+        } else {
+            WindowUpdate::Show {
+                candidates: snapshot.suggestions.clone(),
+                selection: snapshot.selection,
+                page_size: self.page_size,
+            }
+        }
+    }
+
+    fn edit(&mut self, f: impl FnOnce(&mut dyn Converter) -> RawComposingText) {
+        self.composing_text = f(self.converter.as_mut());
+        self.selection = 0;
+        self.outdated = true;
+        self.segments.clear();
+        self.focused = 0;
+        self.converting = false;
+    }
+
+    // the live style converts while typing, the explicit style and the modes with their own
+    // script wait for the selection actions
+    fn shows_conversion(&self) -> bool {
+        (self.conversion_style == ConversionStyle::Live && self.mode_script.is_none())
+            || self.converting
+    }
+
+    fn ensure_segments(&mut self) {
+        if self.segments.is_empty() {
+            self.segments = self.split(0);
+            self.focused = 0;
+        }
+    }
+
+    // change the length of the focused segment, the following segments are split again
+    fn resize(&mut self, offset: i32) {
+        let Some(focused) = self.segments.get(self.focused) else {
+            return;
+        };
+        let start: usize = self.segments[..self.focused].iter().map(|s| s.len).sum();
+        let rest = self.composing_text.text.chars().count() - start;
+        let len = (focused.len as i32)
+            .saturating_add(offset)
+            .clamp(1, rest as i32) as usize;
+
+        let reading: String = self
+            .composing_text
+            .text
+            .chars()
+            .skip(start)
+            .take(len)
+            .collect();
+        let segment = self.segment(reading);
+        self.segments.truncate(self.focused);
+        self.segments.push(segment);
+
+        let following = self.split(start + len);
+        self.segments.extend(following);
+    }
+
+    // split the reading after start (in characters) into clauses
+    fn split(&mut self, start: usize) -> Vec<Segment> {
+        let chars: Vec<char> = self.composing_text.text.chars().collect();
+        let mut segments = vec![];
+        let mut start = start;
+
+        while start < chars.len() {
+            let rest: String = chars[start..].iter().collect();
+            let len = self
+                .converter
+                .first_clause(&rest)
+                .clamp(1, chars.len() - start);
+            segments.push(self.segment(chars[start..start + len].iter().collect()));
+            start += len;
+        }
+
+        segments
+    }
+
+    fn segment(&mut self, reading: String) -> Segment {
+        Segment {
+            len: reading.chars().count(),
+            suggestions: self.converter.segment_candidates(&reading),
+            selection: 0,
+            script: None,
+        }
+    }
+
+    // suggestions which the selection actions work on
+    fn focused_list(&mut self) -> (&Vec<Suggestion>, &mut i32) {
+        match self.segments.get_mut(self.focused) {
+            Some(segment) => {
+                segment.script = None;
+                (&segment.suggestions, &mut segment.selection)
+            }
+            None => (&self.suggestions, &mut self.selection),
+        }
+    }
+
+    // request suggestions only once for a batch of actions
+    fn refresh(&mut self) {
+        if !self.outdated {
+            return;
+        }
+
+        self.suggestions = if self.composing_text.text.is_empty() {
+            vec![]
+        } else {
+            self.converter.candidates()
+        };
+        self.outdated = false;
+    }
+
+    fn select(&mut self, index: i32) {
+        let (suggestions, selection) = self.focused_list();
+        let last = suggestions.len() as i32 - 1;
+        *selection = index.clamp(0, last.max(0));
+    }
+}
+
+// the converter moves the cursor by i8, offsets from the client are cut to its range
+fn clamp_offset(offset: i32) -> i8 {
+    offset.clamp(i8::MIN.into(), i8::MAX.into()) as i8
+}
+
+#[cfg(test)]
+mod tests {
+    use protos::proto::action::RemoveText;
+
+    use super::*;
+    use crate::converter::stub::StubConverter;
+
+    fn session(page_size: u32) -> Session {
+        Session::new(
+            Box::new(StubConverter::default()),
+            page_size,
+            ConversionStyle::Live,
+            InputStyle::Romaji,
+        )
+    }
+
+    fn append(text: &str) -> Kind {
+        Kind::AppendText(text.to_string())
+    }
+
+    #[test]
+    fn sessions_are_independent() {
+        let mut first = session(0);
+        let mut second = session(0);
+
+        first.apply(append("kanji"));
+        second.apply(append("sushi"));
+        first.apply(Kind::RemoveText(RemoveText {}));
+        second.apply(Kind::MoveCursor(-1));
+        first.apply(Kind::SelectSuggestion(1));
+        second.apply(append("ka"));
+
+        let first = first.snapshot();
+        let second = second.snapshot();
+        assert_eq!(first.spell, "かん");
+        assert_eq!(
+            (first.preview.as_str(), first.suffix.as_str()),
+            ("か", "ん")
+        );
+        assert_eq!(first.selection, 1);
+        assert_eq!(second.spell, "すかし");
+        assert_eq!(second.cursor, 2);
+        assert_eq!(second.selection, 0);
+    }
+
+    #[test]
+    fn clearing_a_session_keeps_the_other() {
+        let mut first = session(0);
+        let mut second = session(0);
+
+        first.apply(append("sushi"));
+        second.apply(append("sushi"));
+        first.apply(Kind::ClearText(ClearText {}));
+        second.apply(Kind::ShrinkText(1));
+
+        assert_eq!(first.snapshot().spell, "");
+        assert!(first.snapshot().suggestions.is_empty());
+        assert_eq!(second.snapshot().spell, "し");
+    }
+
+    #[test]
+    fn reverse_lookup() {
+        let mut session = session(0);
+
+        // the text comes first, followed by the conversions of its reading
+        assert_eq!(session.reverse_lookup("カンジ").as_deref(), Some("かんじ"));
+        let snapshot = session.snapshot();
+        assert_eq!(snapshot.spell, "かんじ");
+        assert_eq!(snapshot.preview, "カンジ");
+        let texts: Vec<&str> = snapshot
+            .suggestions
+            .iter()
+            .take(3)
+            .map(|suggestion| suggestion.text.as_str())
+            .collect();
+        assert_eq!(texts, ["カンジ", "漢字", "かんじ"]);
+
+        // the text which is its own reading is not repeated
+        assert_eq!(session.reverse_lookup("かんじ").as_deref(), Some("かんじ"));
+        assert_eq!(
+            session
+                .snapshot()
+                .suggestions
+                .iter()
+                .filter(|suggestion| suggestion.text == "かんじ" && suggestion.subtext.is_empty())
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn unknown_reading() {
+        let mut session = session(0);
+        session.apply(append("kana"));
+
+        // the text is not taken as its own reading, and the session is kept
+        // kanji is unknown as it is to azookey, which can't look the words up by their text
+        assert_eq!(session.reverse_lookup("漢字"), None);
+        assert_eq!(session.reverse_lookup("abc"), None);
+        assert_eq!(session.snapshot().spell, "かな");
+    }
+
+    #[test]
+    fn page_size() {
+        assert_eq!(session(0).page_size, DEFAULT_PAGE_SIZE);
+        assert_eq!(session(5).page_size, 5);
+        // too large for the pages, which are counted in i32
+        assert_eq!(session(u32::MAX).page_size, DEFAULT_PAGE_SIZE);
+        assert_eq!(session(i32::MAX as u32).page_size, i32::MAX);
+
+        let mut session = session(i32::MAX as u32);
+        session.apply(append("abc"));
+        session.apply(Kind::MovePage(1));
+        assert_eq!(session.snapshot().selection, 0);
+    }
+
+    #[test]
+    fn large_offsets() {
+        let mut session = session(2);
+        session.apply(append("kanji"));
+
+        // 256 would wrap to 0
+        session.apply(Kind::MoveCursor(-256));
+        assert_eq!(session.snapshot().cursor, 0);
+        session.apply(Kind::MoveCursor(i32::MAX));
+        assert_eq!(session.snapshot().cursor, 3);
+
+        session.apply(Kind::MoveSelection(i32::MAX));
+        assert_eq!(session.snapshot().selection, 3);
+        session.apply(Kind::MoveSelection(i32::MIN));
+        assert_eq!(session.snapshot().selection, 0);
+        session.apply(Kind::MovePage(i32::MAX));
+        assert_eq!(session.snapshot().selection, 2);
+        session.apply(Kind::MovePage(i32::MIN));
+        assert_eq!(session.snapshot().selection, 0);
+
+        session.apply(Kind::ResizeSegment(i32::MAX));
+        assert_eq!(session.snapshot().segments.len(), 1);
+        session.apply(Kind::ResizeSegment(i32::MIN));
+        session.apply(Kind::MoveSegment(i32::MAX));
+        assert_eq!(session.snapshot().focused_segment, 1);
+        session.apply(Kind::MoveSegment(i32::MIN));
+        assert_eq!(session.snapshot().focused_segment, 0);
+    }
+
+    #[test]
+    fn span_candidates() {
+        let mut session = session(0);
+        session.apply(append("kanjihenkann"));
+
+        let texts = |suggestions: Vec<Suggestion>| -> Vec<String> {
+            suggestions
+                .into_iter()
+                .map(|suggestion| suggestion.text)
+                .collect()
+        };
+        assert_eq!(texts(session.span_candidates(0, 3)), ["漢字", "かんじ"]);
+        assert_eq!(texts(session.span_candidates(3, 4)), ["変換", "へんかん"]);
+        // the span is cut at the end of the spell
+        assert_eq!(texts(session.span_candidates(5, 10)), ["かん"]);
+        assert!(session.span_candidates(7, 1).is_empty());
+
+        // the composing text and its suggestions are left as they are
+        let snapshot = session.snapshot();
+        assert_eq!(snapshot.spell, "かんじへんかん");
+        assert!(snapshot.segments.is_empty());
+        assert_eq!(snapshot.suggestions[0].text, "かんじへんかん");
+    }
+
+    #[test]
+    fn mode_script() {
+        let mut session = session(0);
+        session.apply(Kind::ModeScript(Script::Katakana as i32));
+        session.apply(append("kanji"));
+
+        // the reading is typed in katakana, without the candidate window
+        let snapshot = session.snapshot();
+        assert_eq!(snapshot.preview, "カンジ");
+        assert!(matches!(
+            session.window_update(&snapshot),
+            WindowUpdate::Hide
+        ));
+
+        session.apply(Kind::MoveSelection(1));
+        let snapshot = session.snapshot();
+        assert_eq!(snapshot.preview, "かんじ");
+        assert!(matches!(
+            session.window_update(&snapshot),
+            WindowUpdate::Show { .. }
+        ));
+
+        // any edit goes back to the script, and hides the window again
+        session.apply(Kind::MoveCursor(0));
+        let snapshot = session.snapshot();
+        assert_eq!(snapshot.preview, "カンジ");
+        assert!(matches!(
+            session.window_update(&snapshot),
+            WindowUpdate::Hide
+        ));
+
+        session.apply(Kind::ModeScript(Script::Unspecified as i32));
+        assert_eq!(session.snapshot().preview, "漢字");
+    }
+}
@@ -0,0 +1,148 @@
+use protos::proto::{
+    window_service_client::WindowServiceClient, CandidateRow, CandidateSource, EmptyResponse,
+    SetCandidateRequest, SetSelectionRequest, Suggestion,
+};
+use tokio::sync::mpsc;
+use tonic::transport::Channel;
+
+#[derive(Debug)]
+pub enum WindowUpdate {
+    Show {
+        candidates: Vec<Suggestion>,
+        selection: i32,
+        page_size: i32,
+    },
+    Hide,
+}
+
+// pushes updates to the candidate window server
+// updates are sent in order by a background task, so that ProcessKey doesn't wait for the window
+#[derive(Debug, Clone)]
+pub struct CandidateWindow {
+    sender: mpsc::UnboundedSender<WindowUpdate>,
+}
+
+impl CandidateWindow {
+    pub fn spawn(addr: &'static str) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(run(addr, receiver));
+
+        Self { sender }
+    }
+
+    pub fn update(&self, update: WindowUpdate) {
+        if self.sender.send(update).is_err() {
+            println!("Candidate window task is not running");
+        }
+    }
+}
+
+async fn run(addr: &'static str, mut receiver: mpsc::UnboundedReceiver<WindowUpdate>) {
+    let mut client: Option<WindowServiceClient<Channel>> = None;
+    // skip the requests which don't change the window
+    let mut last_page: Option<SetCandidateRequest> = None;
+    let mut visible: Option<bool> = None;
+
+    while let Some(update) = receiver.recv().await {
+        // the candidate window may be started after the server, so connect lazily
+        if client.is_none() {
+            match WindowServiceClient::connect(addr).await {
+                Ok(connected) => client = Some(connected),
+                Err(e) => {
+                    println!("Failed to connect to the candidate window: {}", e);
+                    continue;
+                }
+            }
+        }
+        let Some(window) = client.as_mut() else {
+            continue;
+        };
+
+        let result = async {
+            match update {
+                WindowUpdate::Show {
+                    candidates,
+                    selection,
+                    page_size,
+                } => {
+                    let request = page(&candidates, selection, page_size);
+                    if last_page.as_ref() != Some(&request) {
+                        window.set_candidate(request.clone()).await?;
+                        last_page = Some(request);
+                    }
+                    window
+                        .set_selection(SetSelectionRequest {
+                            index: selection % page_size,
+                        })
+                        .await?;
+                    if visible != Some(true) {
+                        window.show_window(EmptyResponse {}).await?;
+                        visible = Some(true);
+                    }
+                }
+                WindowUpdate::Hide => {
+                    if visible != Some(false) {
+                        window.hide_window(EmptyResponse {}).await?;
+                        visible = Some(false);
+                    }
+                }
+            }
+
+            Ok::<(), tonic::Status>(())
+        }
+        .await;
+
+        // reconnect with the next update, the window may have been restarted
+        if let Err(e) = result {
+            println!("Failed to update the candidate window: {}", e);
+            client = None;
+            last_page = None;
+            visible = None;
+        }
+    }
+}
+
+// only the page which contains the selection is sent
+pub fn page(candidates: &[Suggestion], selection: i32, page_size: i32) -> SetCandidateRequest {
+    let page = selection / page_size;
+    SetCandidateRequest {
+        rows: candidates
+            .iter()
+            .skip((page * page_size) as usize)
+            .take(page_size as usize)
+            .enumerate()
+            .map(row)
+            .collect(),
+        page: page + 1,
+        page_count: candidates.len().div_ceil(page_size as usize) as i32,
+    }
+}
+
+fn row((index, suggestion): (usize, &Suggestion)) -> CandidateRow {
+    CandidateRow {
+        text: suggestion.text.clone(),
+        // the reading is redundant when the candidate is the reading itself
+        reading: if suggestion.reading == suggestion.text {
+            String::new()
+        } else {
+            suggestion.reading.clone()
+        },
+        annotation: suggestion.annotation.clone(),
+        badge: badge(suggestion.source()).to_string(),
+        // only 1-9 of each page can be selected by the number keys
+        shortcut: if index < 9 {
+            (index + 1).to_string()
+        } else {
+            String::new()
+        },
+    }
+}
+
+fn badge(source: CandidateSource) -> &'static str {
+    match source {
+        CandidateSource::Unspecified | CandidateSource::SystemDictionary => "",
+        CandidateSource::Learned => "学習",
+        CandidateSource::Emoji => "絵文字",
+        CandidateSource::UserDictionary => "ユーザー",
+    }
+}
@@ -4,7 +4,9 @@ use anyhow::Context as _;
 use protos::proto::window_service_server::{
     WindowService as WindowServiceProto, WindowServiceServer,
 };
-use protos::proto::{EmptyResponse, SetCandidateRequest, SetPositionRequest, SetSelectionRequest};
+use protos::proto::{
+    CandidateRow, EmptyResponse, SetCandidateRequest, SetPositionRequest, SetSelectionRequest,
+};
 use tao::dpi::{PhysicalPosition, PhysicalSize};
 use tao::platform::windows::{
     EventLoopBuilderExtWindows, WindowBuilderExtWindows, WindowExtWindows,
@@ -43,9 +45,40 @@ impl WindowController {
 enum WindowAction {
     Show,
     Hide,
-    SetPosition { x: i32, y: i32 },
-    SetSelection { index: i32 },
-    SetCandidate { candidates: Vec<String> },
+    SetPosition {
+        x: i32,
+        y: i32,
+    },
+    SetSelection {
+        index: i32,
+    },
+    SetCandidate {
+        candidates: Vec<Candidate>,
+        page: i32,
+        page_count: i32,
+    },
+}
+
+// updateCandidates に渡す変換候補
+#[derive(Debug, serde::Serialize)]
+struct Candidate {
+    text: String,
+    reading: String,
+    annotation: String,
+    badge: String,
+    shortcut: String,
+}
+
+impl From<CandidateRow> for Candidate {
+    fn from(row: CandidateRow) -> Self {
+        Self {
+            text: row.text,
+            reading: row.reading,
+            annotation: row.annotation,
+            badge: row.badge,
+            shortcut: row.shortcut,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -98,12 +131,15 @@ impl WindowServiceProto for WindowService {
         &self,
         request: Request<SetCandidateRequest>,
     ) -> Result<Response<EmptyResponse>, Status> {
-        let candidate = request.into_inner().candidates;
+        let request = request.into_inner();
+        let candidates = request.rows.into_iter().map(Candidate::from).collect();
 
         self.controller
             .sender
             .send(WindowAction::SetCandidate {
-                candidates: candidate,
+                candidates,
+                page: request.page,
+                page_count: request.page_count,
             })
             .await
             .unwrap();
@@ -192,7 +228,6 @@ async fn main() -> anyhow::Result<()> {
                             scroll-snap-type: y proximity;
                             list-style-position: inside;
                             list-style-type: none;
-                            counter-reset: number 0;
                             user-select: none;
                             cursor: pointer;
 
@@ -212,15 +247,29 @@ async fn main() -> anyhow::Result<()> {
                             align-items: center;
                             scroll-snap-align: start;
 
-                            &::before {
-                                content: counter(number);
-                                counter-increment: number 1;
+                            .shortcut {
+                                min-width: 0.5rem;
                                 color: #636363;
                                 font-weight: bold;
                                 font-size: 0.75rem;
                                 margin: 0 0.75rem 0 2;
                             }
 
+                            .reading, .annotation {
+                                color: #838384;
+                                font-size: 0.7rem;
+                                margin-left: 0.5rem;
+                            }
+
+                            .badge {
+                                margin-left: auto;
+                                padding: 0 0.3rem;
+                                border: 1px solid #BCBCBC;
+                                border-radius: 3px;
+                                color: #636363;
+                                font-size: 0.65rem;
+                            }
+
                             &[data-selected] {
                                 background-color: #D4F0FF;
                                 border-radius: 3px;
@@ -229,6 +278,9 @@ async fn main() -> anyhow::Result<()> {
                                 outline-offset: -1px;
                             }
                         }
+                        #page {
+                            color: #636363;
+                        }
                         footer {
                             display: flex;
                             justify-content: space-between;
@@ -245,11 +297,31 @@ async fn main() -> anyhow::Result<()> {
                             candidateList.innerHTML = '';
                             candidates.forEach((candidate) => {
                                 const li = document.createElement('li');
-                                li.textContent = candidate;
+                                // 空の項目は表示しない
+                                const parts = [
+                                    ['shortcut', candidate.shortcut],
+                                    ['text', candidate.text],
+                                    ['reading', candidate.reading],
+                                    ['annotation', candidate.annotation],
+                                    ['badge', candidate.badge],
+                                ];
+                                parts.forEach(([className, text]) => {
+                                    if (className !== 'shortcut' && className !== 'text' && !text) {
+                                        return;
+                                    }
+                                    const span = document.createElement('span');
+                                    span.className = className;
+                                    span.textContent = text;
+                                    li.appendChild(span);
+                                });
                                 candidateList.appendChild(li);
                             });
                         }
 
+                        function updatePage(page, pageCount) {
+                            document.getElementById('page').textContent = `${page}/${pageCount}`;
+                        }
+
                         function updateSelection(index) {
                             const candidateList = document.getElementById('candidate-list');
                             const selected = candidateList.querySelector('[data-selected]');
@@ -269,6 +341,7 @@ async fn main() -> anyhow::Result<()> {
                             <svg width="20" height="14" viewBox="0 0 22 16" fill="none" xmlns="http://www.w3.org/2000/svg">
                                 <path d="M3.5 8C4.59202 9.04403 7.54398 10.3978 13.5068 9.93754M1.25349 5.39919C2.77722 0.413397 8.08911 0.79692 10.9673 1.24436C14.2687 1.71311 20.8969 3.82675 20.9985 8.53129C21.1255 14.412 13.1894 15.3069 10.0784 14.9233C6.96748 14.5398 -0.46071 13.0696 1.25349 5.39919Z" stroke="#838384" stroke-width="1.5" stroke-linecap="round"/>
                             </svg>
+                            <span id="page"></span>
                         </footer>
                     </main>
                 </body>
@@ -320,13 +393,26 @@ async fn main() -> anyhow::Result<()> {
                 WindowAction::SetPosition { x, y } => {
                     window.set_outer_position(PhysicalPosition::new((x - 15) as f64, y as f64));
                 }
-                WindowAction::SetCandidate { candidates } => {
+                WindowAction::SetCandidate {
+                    candidates,
+                    page,
+                    page_count,
+                } => {
+                    // 読みや注釈、バッジは小さい文字なので半分の幅で見積もる
                     let max_len = candidates
                         .iter()
-                        .map(|s| s.chars().count())
+                        .map(|c| {
+                            c.text.chars().count()
+                                + (c.reading.chars().count()
+                                    + c.annotation.chars().count()
+                                    + c.badge.chars().count())
+                                    / 2
+                        })
                         .max()
                         .unwrap_or(0) as u32;
-                    window.set_inner_size(PhysicalSize::new(max(225, 120 + max_len * 18), 275));
+                    // ページ全体がスクロールせずに収まる高さにする
+                    let height = max(140, 70 + candidates.len() as u32 * 35);
+                    window.set_inner_size(PhysicalSize::new(max(225, 120 + max_len * 18), height));
 
                     let candidates = serde_json::to_string(&candidates)
                         .context("Failed to serialize candidates")
@@ -335,6 +421,9 @@ async fn main() -> anyhow::Result<()> {
                     event_loop_proxy
                         .send_event(format!("updateCandidates({})", candidates))
                         .unwrap();
+                    event_loop_proxy
+                        .send_event(format!("updatePage({}, {})", page, page_count))
+                        .unwrap();
                 }
                 WindowAction::SetSelection { index } => {
                     event_loop_proxy
@@ -3,7 +3,9 @@ import Foundation
 import ffi
 
 @MainActor let converter = KanaKanjiConverter()
-@MainActor var composingText = ComposingText()
+// composing text of each session, created by CreateSession
+@MainActor var composingTexts: [Int32: ComposingText] = [:]
+@MainActor var lastSessionId: Int32 = 0
 
 @MainActor var execURL = URL(filePath: "")
 
@@ -50,7 +52,11 @@ struct SComposingText {
 
 // struct FFICandidate {
 //     char *text;
+//     char *subtext;
+//     char *reading;
+//     char *annotation;
 //     int correspondingCount;
+//     int source;
 // };
 
 func constructCandidateString(candidate: Candidate, hiragana: String) -> String {
@@ -69,6 +75,50 @@ func constructCandidateString(candidate: Candidate, hiragana: String) -> String
     return result
 }
 
+// ruby of the dictionary is katakana, so show it in hiragana
+func candidateReading(candidate: Candidate) -> String {
+    let ruby = candidate.data.map { $0.ruby }.joined()
+    return ruby.applyingTransform(.hiraganaToKatakana, reverse: true) ?? ruby
+}
+
+func isEmoji(_ text: String) -> Bool {
+    return !text.isEmpty && text.unicodeScalars.allSatisfy({ $0.properties.isEmojiPresentation || $0.properties.isEmojiModifier || $0.value == 0x200D || $0.value == 0xFE0F })
+}
+
+// the constants of ffi.h mirror CandidateSource in service.proto
+func candidateSource(candidate: Candidate) -> Int32 {
+    let source: FFICandidateSource
+    if candidate.data.contains(where: { $0.metadata.contains(.isFromUserDictionary) }) {
+        source = CANDIDATE_SOURCE_USER_DICTIONARY
+    } else if candidate.data.contains(where: { $0.metadata.contains(.isLearned) }) {
+        source = CANDIDATE_SOURCE_LEARNED
+    } else if isEmoji(candidate.text) {
+        source = CANDIDATE_SOURCE_EMOJI
+    } else {
+        source = CANDIDATE_SOURCE_SYSTEM_DICTIONARY
+    }
+    return Int32(source.rawValue)
+}
+
+// the unicode name of an emoji, and the code point of a symbol which looks like others (e.g. 〜 and ～)
+// empty for words, which are told apart by the reading
+func candidateAnnotation(candidate: Candidate) -> String {
+    let scalars = Array(candidate.text.unicodeScalars)
+    if isEmoji(candidate.text) {
+        return scalars.compactMap { $0.properties.name?.lowercased() }.joined(separator: " + ")
+    }
+    guard scalars.count == 1, let scalar = scalars.first, !scalar.isASCII else {
+        return ""
+    }
+    switch scalar.properties.generalCategory {
+    case .mathSymbol, .currencySymbol, .modifierSymbol, .otherSymbol, .dashPunctuation, .otherPunctuation:
+        let codePoint = "U+" + String(scalar.value, radix: 16, uppercase: true)
+        return [codePoint, scalar.properties.name].compactMap { $0 }.joined(separator: " ")
+    default:
+        return ""
+    }
+}
+
 @_silgen_name("Initialize")
 @MainActor public func initialize(
     path: UnsafePointer<CChar>
@@ -77,44 +127,61 @@ func constructCandidateString(candidate: Candidate, hiragana: String) -> String
     execURL = URL(filePath: path)
 }
 
+@_silgen_name("CreateSession")
+@MainActor public func create_session() -> Int32 {
+    lastSessionId += 1
+    composingTexts[lastSessionId] = ComposingText()
+    return lastSessionId
+}
+
+@_silgen_name("DestroySession")
+@MainActor public func destroy_session(session: Int32) {
+    composingTexts.removeValue(forKey: session)
+}
+
 @_silgen_name("AppendText")
 @MainActor public func append_text(
+    session: Int32,
     input: UnsafePointer<CChar>,
-    cursorPtr: UnsafeMutablePointer<Int>
+    kana: Bool,
+    cursorPtr: UnsafeMutablePointer<Int32>
 ) -> UnsafeMutablePointer<CChar> {
     let inputString = String(cString: input)
-    composingText.insertAtCursorPosition(inputString, inputStyle: .roman2kana)
+    // the kana input style types kana itself, which roman2kana must not touch
+    composingTexts[session, default: ComposingText()].insertAtCursorPosition(inputString, inputStyle: kana ? .direct : .roman2kana)
 
-    cursorPtr.pointee = composingText.convertTargetCursorPosition    
+    let composingText = composingTexts[session, default: ComposingText()]
+    cursorPtr.pointee = Int32(composingText.convertTargetCursorPosition)    
     return _strdup(composingText.convertTarget)!
 }
 
 @_silgen_name("RemoveText")
 @MainActor public func remove_text(
-    cursorPtr: UnsafeMutablePointer<Int>
+    session: Int32,
+    cursorPtr: UnsafeMutablePointer<Int32>
 ) -> UnsafeMutablePointer<CChar> {
-    composingText.deleteBackwardFromCursorPosition(count: 1)
+    composingTexts[session, default: ComposingText()].deleteBackwardFromCursorPosition(count: 1)
 
-    cursorPtr.pointee = composingText.convertTargetCursorPosition
+    let composingText = composingTexts[session, default: ComposingText()]
+    cursorPtr.pointee = Int32(composingText.convertTargetCursorPosition)
     return _strdup(composingText.convertTarget)!
 }
 
 @_silgen_name("MoveCursor")
 @MainActor public func move_cursor(
+    session: Int32,
     offset: Int32,
-    cursorPtr: UnsafeMutablePointer<Int>
+    cursorPtr: UnsafeMutablePointer<Int32>
 ) -> UnsafeMutablePointer<CChar> {
-    let previousCursor = composingText.convertTargetCursorPosition
-    let cursor = composingText.moveCursorFromCursorPosition(count: Int(offset))
-    print("offset: \(offset), cursor: \(cursor)")
+    let cursor = composingTexts[session, default: ComposingText()].moveCursorFromCursorPosition(count: Int(offset))
 
-    cursorPtr.pointee = cursor
-    return _strdup(composingText.convertTarget)!
+    cursorPtr.pointee = Int32(cursor)
+    return _strdup(composingTexts[session, default: ComposingText()].convertTarget)!
 }
 
 @_silgen_name("ClearText")
-@MainActor public func clear_text() {
-    composingText = ComposingText()
+@MainActor public func clear_text(session: Int32) {
+    composingTexts[session] = ComposingText()
 }
 
 func to_list_pointer(_ list: [FFICandidate]) -> UnsafeMutablePointer<UnsafeMutablePointer<FFICandidate>?> {
@@ -126,33 +193,132 @@ func to_list_pointer(_ list: [FFICandidate]) -> UnsafeMutablePointer<UnsafeMutab
     return pointer
 }
 
-@_silgen_name("GetComposedText")
-@MainActor public func get_composed_text(lengthPtr: UnsafeMutablePointer<Int>) -> UnsafeMutablePointer<UnsafeMutablePointer<FFICandidate>?> {
-    let hiragana = composingText.convertTarget
+func makeFFICandidate(candidate: Candidate, composingText: ComposingText) -> FFICandidate {
+    let text = strdup(constructCandidateString(candidate: candidate, hiragana: composingText.convertTarget))
+    let correspondingCount = candidate.correspondingCount
+
+    var afterComposingText = composingText
+    afterComposingText.prefixComplete(correspondingCount: correspondingCount)
+    let subtext = strdup(afterComposingText.convertTarget)
+
+    let reading = strdup(candidateReading(candidate: candidate))
+    let annotation = strdup(candidateAnnotation(candidate: candidate))
+    let source = candidateSource(candidate: candidate)
+
+    return FFICandidate(text: text, subtext: subtext, reading: reading, annotation: annotation, correspondingCount: Int32(correspondingCount), source: source)
+}
+
+// the reading of a segment is already kana, so it is not processed by roman2kana
+func segmentComposingText(reading: UnsafePointer<CChar>) -> ComposingText {
+    var composingText = ComposingText()
+    composingText.insertAtCursorPosition(String(cString: reading), inputStyle: .direct)
+    return composingText
+}
+
+@_silgen_name("GetSegmentCandidates")
+@MainActor public func get_segment_candidates(
+    reading: UnsafePointer<CChar>,
+    lengthPtr: UnsafeMutablePointer<Int32>
+) -> UnsafeMutablePointer<UnsafeMutablePointer<FFICandidate>?> {
+    let composingText = segmentComposingText(reading: reading)
     let converted = converter.requestCandidates(composingText, options: options)
     var result: [FFICandidate] = []
 
-    for i in 0..<converted.mainResults.count {
-        let candidate = converted.mainResults[i]
+    // only the candidates which cover the whole segment
+    for candidate in converted.mainResults where candidate.correspondingCount == composingText.input.count {
+        result.append(makeFFICandidate(candidate: candidate, composingText: composingText))
+    }
+
+    lengthPtr.pointee = Int32(result.count)
+
+    return to_list_pointer(result)
+}
 
-        let text = strdup(constructCandidateString(candidate: candidate, hiragana: hiragana))
-        let correspondingCount = candidate.correspondingCount
+@_silgen_name("GetFirstClause")
+@MainActor public func get_first_clause(reading: UnsafePointer<CChar>) -> Int32 {
+    let composingText = segmentComposingText(reading: reading)
+    let converted = converter.requestCandidates(composingText, options: options)
+
+    // with the direct input style, an input element is a character
+    let count = converted.firstClauseResults.first?.correspondingCount ?? composingText.input.count
+    return Int32(count)
+}
 
-        var afterComposingText = composingText
-        afterComposingText.prefixComplete(correspondingCount: correspondingCount)
-        let subtext = strdup(afterComposingText.convertTarget)
+@_silgen_name("GetComposedText")
+@MainActor public func get_composed_text(
+    session: Int32,
+    lengthPtr: UnsafeMutablePointer<Int32>
+) -> UnsafeMutablePointer<UnsafeMutablePointer<FFICandidate>?> {
+    let composingText = composingTexts[session, default: ComposingText()]
+    let converted = converter.requestCandidates(composingText, options: options)
+    var result: [FFICandidate] = []
 
-        result.append(FFICandidate(text: text, subtext: subtext, correspondingCount: Int32(correspondingCount)))        
+    for candidate in converted.mainResults {
+        result.append(makeFFICandidate(candidate: candidate, composingText: composingText))
     }
 
-    lengthPtr.pointee = result.count
+    lengthPtr.pointee = Int32(result.count)
 
     return to_list_pointer(result)
 }
 
 @_silgen_name("ShrinkText")
-@MainActor public func shrink_text(offset: Int32) {
-    var afterComposingText = composingText
+@MainActor public func shrink_text(
+    session: Int32,
+    offset: Int32,
+    cursorPtr: UnsafeMutablePointer<Int32>
+) -> UnsafeMutablePointer<CChar> {
+    var afterComposingText = composingTexts[session, default: ComposingText()]
     afterComposingText.prefixComplete(correspondingCount: Int(offset))
-    composingText = afterComposingText
-}
\ No newline at end of file
+    composingTexts[session] = afterComposingText
+
+    cursorPtr.pointee = Int32(afterComposingText.convertTargetCursorPosition)
+    return _strdup(afterComposingText.convertTarget)!
+}
+
+@_silgen_name("GetRawInput")
+@MainActor public func get_raw_input(session: Int32) -> UnsafeMutablePointer<CChar> {
+    let composingText = composingTexts[session, default: ComposingText()]
+    let input = composingText.input.map { String($0.character) }.joined()
+    return _strdup(input)!
+}
+
+// reading of a committed text for the reconversion
+// the dictionary can't be looked up by the word, so the reading is only known for kana
+// NULL if the text has anything else, which the reconversion doesn't support
+@_silgen_name("GetReading")
+@MainActor public func get_reading(text: UnsafePointer<CChar>) -> UnsafeMutablePointer<CChar>? {
+    let text = String(cString: text)
+    guard let reading = text.applyingTransform(.hiraganaToKatakana, reverse: true),
+          !reading.isEmpty,
+          reading.unicodeScalars.allSatisfy({ (0x3041...0x309E).contains($0.value) || $0.value == 0x30FC })
+    else {
+        return nil
+    }
+    return _strdup(reading)!
+}
+
+// the strings returned above are allocated by strdup, the caller gives them back here
+@_silgen_name("FreeString")
+public func free_string(ptr: UnsafeMutablePointer<CChar>?) {
+    free(ptr)
+}
+
+// the lists returned by to_list_pointer, with the strings of each candidate
+@_silgen_name("FreeCandidates")
+public func free_candidates(
+    list: UnsafeMutablePointer<UnsafeMutablePointer<FFICandidate>?>,
+    length: Int32
+) {
+    for i in 0..<Int(length) {
+        guard let candidate = list[i] else {
+            continue
+        }
+        free(candidate.pointee.text)
+        free(candidate.pointee.subtext)
+        free(candidate.pointee.reading)
+        free(candidate.pointee.annotation)
+        candidate.deallocate()
+    }
+    list.deallocate()
+}
@@ -5,8 +5,20 @@
 
 #endif /* ffi_h */
 
+// the values of CandidateSource in crates/protos/service.proto, which the rust side reads
+enum FFICandidateSource {
+    CANDIDATE_SOURCE_UNSPECIFIED = 0,
+    CANDIDATE_SOURCE_SYSTEM_DICTIONARY = 1,
+    CANDIDATE_SOURCE_LEARNED = 2,
+    CANDIDATE_SOURCE_EMOJI = 3,
+    CANDIDATE_SOURCE_USER_DICTIONARY = 4,
+};
+
 struct FFICandidate {
     char *text;
     char *subtext;
+    char *reading;
+    char *annotation;
     int correspondingCount;
+    int source;
 };
\ No newline at end of file
@@ -1,6 +1,26 @@
+import Foundation
 import Testing
 @testable import azookey_server
 
-@Test func example() async throws {
-    // Write your test here and use APIs like `#expect(...)` to check expected conditions.
+// GetReading as the rust side calls it, the returned string is given back by FreeString
+@MainActor func reading(_ text: String) -> String? {
+    guard let result = text.withCString({ get_reading(text: $0) }) else {
+        return nil
+    }
+    defer { free_string(ptr: result) }
+    return String(cString: result)
+}
+
+@MainActor @Test func readingOfKana() {
+    #expect(reading("ひらがな") == "ひらがな")
+    #expect(reading("カタカナ") == "かたかな")
+    #expect(reading("ラーメン") == "らーめん")
+}
+
+// the dictionary can't be looked up by the word, so the reconversion of kanji fails
+@MainActor @Test func unknownReading() {
+    #expect(reading("漢字") == nil)
+    #expect(reading("かな漢字") == nil)
+    #expect(reading("abc") == nil)
+    #expect(reading("") == nil)
 }